@@ -0,0 +1,9 @@
+fn main() {
+    // intel_tex_2 links a prebuilt C++ object, so executables (tests, binaries) need the C++ runtime
+    let target = std::env::var("TARGET").unwrap();
+    if target.contains("apple") {
+        println!("cargo:rustc-link-lib=c++");
+    } else if target.contains("linux") {
+        println!("cargo:rustc-link-lib=stdc++");
+    }
+}
@@ -0,0 +1,286 @@
+use image::RgbaImage;
+use rayon::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use crate::encode_decode::{decode, encode, generate_commands, prepare_image, reuse_prev_blocks, EncodeOptions};
+use crate::error::{Error, Result};
+use crate::format::{BcFormat, BcQuality, DrawCall};
+use crate::preprocess::ProcessedFrame;
+use crate::sld::SldLayer;
+
+/// Decodes the frames of one layer of an animation in order, keeping the blocks and offsets of the previous frame
+/// so that frames which reuse the previous frame's blocks can be decoded
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Debug, Default)]
+pub struct LayerDecoder {
+    /// (blocks after processing the draw commands, width, height, offset_x1, offset_y1) of the last decoded frame
+    prev: Option<(Vec<u8>, u32, u32, i32, i32)>,
+}
+
+impl LayerDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the previous frame, for use when starting a new animation
+    pub fn reset(&mut self) {
+        self.prev = None;
+    }
+
+    /// Decodes the next frame given its (x1, y1, x2, y2) offsets on the canvas
+    pub fn decode(
+        &mut self,
+        bytes: Vec<u8>,
+        offsets: (u32, u32, u32, u32),
+        format: BcFormat,
+        commands: &[DrawCall],
+        use_prev: bool,
+    ) -> Result<RgbaImage> {
+        let (x1, y1, x2, y2) = offsets;
+        if x2 < x1 || y2 < y1 {
+            return Err(Error::InvalidInput(format!("Invalid offsets ({x1}, {y1}, {x2}, {y2})")));
+        }
+        let (width, height) = (x2 - x1, y2 - y1);
+        let (x1, y1) = (x1 as i32, y1 as i32);
+
+        let prev_info = match (use_prev, &self.prev) {
+            (false, _) => None,
+            (true, Some((prev, prev_width, prev_height, prev_x1, prev_y1))) => {
+                Some((prev.as_slice(), *prev_width, *prev_height, prev_x1 - x1, prev_y1 - y1))
+            }
+            (true, None) => {
+                return Err(Error::InvalidInput(
+                    "Frame uses the previous frame but no frame was decoded before it".to_string()
+                ));
+            }
+        };
+
+        let (image, blocks) = decode(bytes, width, height, format, Some(commands), prev_info)?;
+        self.prev = Some((blocks, width, height, x1, y1));
+
+        Ok(image)
+    }
+
+    pub fn decode_layer(&mut self, layer: &SldLayer) -> Result<RgbaImage> {
+        let header = &layer.header;
+        self.decode(
+            layer.blocks.clone(),
+            (header.offset_x1 as u32, header.offset_y1 as u32, header.offset_x2 as u32, header.offset_y2 as u32),
+            layer.format(),
+            &layer.commands,
+            layer.uses_prev(),
+        )
+    }
+}
+
+/// Decodes all frames of one layer of an animation
+pub fn decode_animation<'a>(layers: impl IntoIterator<Item = &'a SldLayer>) -> Result<Vec<RgbaImage>> {
+    let mut decoder = LayerDecoder::new();
+    layers.into_iter()
+        .map(|layer| decoder.decode_layer(layer))
+        .collect()
+}
+
+/// Encodes the frames of one layer of an animation in order. Each frame is encoded both standalone and with skips
+/// referencing the previous frame, and whichever draws fewer blocks is kept
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Debug)]
+pub struct LayerEncoder {
+    format: BcFormat,
+    quality: BcQuality,
+    options: EncodeOptions,
+    /// (compressed blocks, width, height, offset_x1, offset_y1) of the last encoded frame
+    prev: Option<(Vec<u8>, u32, u32, i32, i32)>,
+}
+
+impl LayerEncoder {
+    pub fn new(format: BcFormat, quality: BcQuality, options: EncodeOptions) -> Self {
+        Self { format, quality, options, prev: None }
+    }
+
+    /// Forgets the previous frame, for use when starting a new animation
+    pub fn reset(&mut self) {
+        self.prev = None;
+    }
+
+    /// Encodes the next frame. Returns the draw commands, the drawn blocks and whether the frame references the
+    /// previous frame (the SLD "use previous frame" flag)
+    pub fn encode(&mut self, frame: &ProcessedFrame) -> Result<(Vec<DrawCall>, Vec<u8>, bool)> {
+        let (prepared, options) = prepare_image(&frame.image, self.format, &self.options);
+        let image = prepared.as_ref().unwrap_or(&frame.image);
+        let (blocks, _) = encode(image, self.format, self.quality, false, None, &options)?;
+        self.encode_compressed(frame, image, blocks)
+    }
+
+    /// Generates the draw commands of the next frame given its already compressed blocks and the image they were
+    /// compressed from, see `prepare_image`
+    fn encode_compressed(
+        &mut self,
+        frame: &ProcessedFrame,
+        image: &RgbaImage,
+        mut blocks: Vec<u8>,
+    ) -> Result<(Vec<DrawCall>, Vec<u8>, bool)> {
+        let (width, height) = (frame.offset_x2 - frame.offset_x1, frame.offset_y2 - frame.offset_y1);
+        let (x1, y1) = (frame.offset_x1 as i32, frame.offset_y1 as i32);
+
+        let (mut commands, mut drawn) = generate_commands(&blocks, width, height, self.format, None);
+        let mut use_prev = false;
+
+        if let Some((prev, prev_width, prev_height, prev_x1, prev_y1)) = &self.prev {
+            let prev_info = (prev.as_slice(), *prev_width, *prev_height, prev_x1 - x1, prev_y1 - y1);
+            let mut reused = blocks.clone();
+            reuse_prev_blocks(&mut reused, image, self.format, prev_info, &self.options)?;
+            let (prev_commands, prev_drawn) = generate_commands(&reused, width, height, self.format, Some(prev_info));
+
+            if (prev_drawn.len(), prev_commands.len()) < (drawn.len(), commands.len()) {
+                (commands, drawn, use_prev, blocks) = (prev_commands, prev_drawn, true, reused);
+            }
+        }
+
+        self.prev = Some((blocks, width, height, x1, y1));
+
+        Ok((commands, drawn, use_prev))
+    }
+}
+
+/// Encodes all frames of one layer of an animation, see `LayerEncoder::encode` for the result of each frame. The
+/// frames are compressed in parallel, only the draw commands are generated in order
+#[allow(clippy::type_complexity)]
+pub fn encode_animation(
+    frames: &[ProcessedFrame],
+    format: BcFormat,
+    quality: BcQuality,
+    options: &EncodeOptions,
+) -> Result<Vec<(Vec<DrawCall>, Vec<u8>, bool)>> {
+    let compressed = frames.par_iter()
+        .map(|frame| {
+            let (prepared, prepared_options) = prepare_image(&frame.image, format, options);
+            let image = prepared.as_ref().unwrap_or(&frame.image);
+            let (blocks, _) = encode(image, format, quality, false, None, &prepared_options)?;
+            Ok((prepared, blocks))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut encoder = LayerEncoder::new(format, quality, *options);
+    frames.iter()
+        .zip(compressed)
+        .map(|(frame, (prepared, blocks))| {
+            encoder.encode_compressed(frame, prepared.as_ref().unwrap_or(&frame.image), blocks)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+    use super::*;
+    use crate::format::AlphaDither;
+
+    /// An 8x8 image with a different color in each of its 4x4 blocks
+    fn blocks(blue: u8) -> RgbaImage {
+        RgbaImage::from_fn(8, 8, |x, y| Rgba([(x / 4 * 120) as u8, (y / 4 * 120) as u8, blue, 255]))
+    }
+
+    #[test]
+    fn decodes_frames_that_reuse_the_previous_frame() {
+        // the second frame is a block further right, its left blocks are the first frame's right blocks
+        let first = blocks(0);
+        let second = RgbaImage::from_fn(8, 8, |x, y| {
+            if x < 4 { *first.get_pixel(x + 4, y) } else { Rgba([0, 0, 200, 255]) }
+        });
+        let options = EncodeOptions::default();
+
+        let (first_blocks, first_encoded) =
+            encode(&first, BcFormat::Bc1, BcQuality::Fast, true, None, &options).unwrap();
+        let prev_info = Some((first_blocks.as_slice(), 8, 8, -4, 0));
+        let (second_blocks, second_encoded) =
+            encode(&second, BcFormat::Bc1, BcQuality::Fast, true, prev_info, &options).unwrap();
+        let ((first_commands, first_drawn), (second_commands, second_drawn)) =
+            (first_encoded.unwrap(), second_encoded.unwrap());
+        assert_eq!(second_drawn.len(), 2 * BcFormat::Bc1.block_size());
+
+        let mut decoder = LayerDecoder::new();
+        let decoded = decoder.decode(first_drawn, (0, 0, 8, 8), BcFormat::Bc1, &first_commands, false).unwrap();
+        assert_eq!(decoded, decode(first_blocks, 8, 8, BcFormat::Bc1, None, None).unwrap().0);
+        let decoded = decoder.decode(second_drawn, (4, 0, 12, 8), BcFormat::Bc1, &second_commands, true).unwrap();
+        assert_eq!(decoded, decode(second_blocks, 8, 8, BcFormat::Bc1, None, None).unwrap().0);
+
+        decoder.reset();
+        assert!(decoder.decode(Vec::new(), (0, 0, 8, 8), BcFormat::Bc1, &[], true).is_err());
+    }
+
+    fn frame(color: [u8; 4]) -> ProcessedFrame {
+        ProcessedFrame {
+            image: RgbaImage::from_pixel(8, 8, Rgba(color)),
+            offset_x1: 0,
+            offset_y1: 0,
+            offset_x2: 8,
+            offset_y2: 8,
+            anchor_x: 4,
+            anchor_y: 4,
+        }
+    }
+
+    fn with_image(image: RgbaImage) -> ProcessedFrame {
+        ProcessedFrame { image, ..frame([0; 4]) }
+    }
+
+    #[test]
+    fn round_trips_animations() {
+        let mut changed = blocks(50);
+        changed.put_pixel(6, 6, Rgba([255, 255, 255, 255]));
+        let frames = [with_image(blocks(0)), with_image(blocks(50)), with_image(changed)];
+
+        let encoded = encode_animation(&frames, BcFormat::Bc1, BcQuality::Fast, &EncodeOptions::default()).unwrap();
+        // only the changed block of the last frame is drawn
+        assert_eq!(encoded.iter().map(|(_, _, use_prev)| *use_prev).collect::<Vec<_>>(), [false, false, true]);
+        assert_eq!(encoded[2].1.len(), BcFormat::Bc1.block_size());
+
+        let layers = frames.iter()
+            .zip(encoded)
+            .map(|(frame, (commands, drawn, use_prev))| {
+                SldLayer::from_encoded((commands, drawn), frame, BcFormat::Bc1, use_prev).unwrap()
+            })
+            .collect::<Vec<_>>();
+        for (frame, decoded) in frames.iter().zip(decode_animation(&layers).unwrap()) {
+            let (blocks, _) = encode(&frame.image, BcFormat::Bc1, BcQuality::Fast, false, None, &Default::default())
+                .unwrap();
+            assert_eq!(decoded, decode(blocks, 8, 8, BcFormat::Bc1, None, None).unwrap().0);
+        }
+    }
+
+    #[test]
+    fn keeps_the_encoding_that_draws_fewer_blocks() {
+        // after an opaque frame, a frame with a single opaque block draws fewer blocks standalone, as its transparent
+        // blocks would have to be drawn to replace the previous frame's. The same frame again is fully skipped
+        let mut sparse = RgbaImage::new(8, 8);
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            sparse.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+        }
+        let frames = [frame([0, 0, 255, 255]), with_image(sparse.clone()), with_image(sparse)];
+
+        let mut encoder = LayerEncoder::new(BcFormat::Bc1, BcQuality::Fast, EncodeOptions::default());
+        let encoded = frames.iter().map(|frame| encoder.encode(frame).unwrap()).collect::<Vec<_>>();
+        let summary = encoded.iter()
+            .map(|(_, drawn, use_prev)| (drawn.len() / BcFormat::Bc1.block_size(), *use_prev))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, [(4, false), (1, false), (0, true)]);
+    }
+
+    #[test]
+    fn reuses_dithered_blocks_within_max_delta() {
+        // the reuse check must compare against the dithered alpha that was compressed, not the semi-transparent source
+        let frames = [frame([200, 100, 50, 160]), frame([210, 100, 50, 160])];
+        let options = EncodeOptions { alpha_dither: AlphaDither::Ordered, max_delta: Some(16), ..Default::default() };
+
+        let encoded = encode_animation(&frames, BcFormat::Bc1, BcQuality::Fast, &options).unwrap();
+        let (_, drawn, use_prev) = &encoded[1];
+        assert!(use_prev);
+        assert!(drawn.is_empty());
+
+        let mut encoder = LayerEncoder::new(BcFormat::Bc1, BcQuality::Fast, options);
+        let sequential = frames.iter().map(|frame| encoder.encode(frame).unwrap()).collect::<Vec<_>>();
+        assert_eq!(sequential[1].2, *use_prev);
+        assert_eq!(sequential[1].1, *drawn);
+    }
+}
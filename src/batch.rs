@@ -0,0 +1,130 @@
+use std::ops::Deref;
+use image::{ImageBuffer, Rgba, RgbaImage};
+use rayon::prelude::*;
+use crate::animation::{decode_animation, encode_animation};
+use crate::encode_decode::{decode, encode, EncodeOptions};
+use crate::error::Result;
+use crate::format::{BcFormat, BcQuality, DrawCall};
+use crate::preprocess::ProcessedFrame;
+use crate::sld::SldLayer;
+
+/// Compresses independent images in parallel, see `encode`. The results are in the same order as the images
+#[allow(clippy::type_complexity)]
+pub fn encode_batch<C: Deref<Target = [u8]> + Sync>(
+    images: &[ImageBuffer<Rgba<u8>, C>],
+    format: BcFormat,
+    quality: BcQuality,
+    gen_commands: bool,
+    options: &EncodeOptions,
+) -> Result<Vec<(Vec<u8>, Option<(Vec<DrawCall>, Vec<u8>)>)>> {
+    images.par_iter()
+        .map(|image| encode(image, format, quality, gen_commands, None, options))
+        .collect()
+}
+
+/// Decompresses independent frames of (bytes, width, height, format, commands) in parallel, see `decode`. The results
+/// are in the same order as the frames
+#[allow(clippy::type_complexity)]
+pub fn decode_batch(
+    frames: Vec<(Vec<u8>, u32, u32, BcFormat, Option<Vec<DrawCall>>)>,
+) -> Result<Vec<(RgbaImage, Vec<u8>)>> {
+    frames.into_par_iter()
+        .map(|(bytes, width, height, format, commands)| {
+            decode(bytes, width, height, format, commands.as_deref(), None)
+        })
+        .collect()
+}
+
+/// Encodes independent animations (e.g. the directions or layers of a unit) in parallel, see `encode_animation`. The
+/// results are in the same order as the animations
+#[allow(clippy::type_complexity)]
+pub fn encode_animations(
+    animations: &[Vec<ProcessedFrame>],
+    format: BcFormat,
+    quality: BcQuality,
+    options: &EncodeOptions,
+) -> Result<Vec<Vec<(Vec<DrawCall>, Vec<u8>, bool)>>> {
+    animations.par_iter()
+        .map(|frames| encode_animation(frames, format, quality, options))
+        .collect()
+}
+
+/// Decodes independent animations in parallel, see `decode_animation`. The results are in the same order as the
+/// animations
+pub fn decode_animations(animations: &[Vec<SldLayer>]) -> Result<Vec<Vec<RgbaImage>>> {
+    animations.par_iter()
+        .map(decode_animation)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calls(commands: &[DrawCall]) -> Vec<(u8, u8)> {
+        commands.iter().map(|cmd| (cmd.skip, cmd.draw)).collect()
+    }
+
+    #[test]
+    fn batches_match_sequential_calls() {
+        let images: Vec<RgbaImage> = (0..4_u8)
+            .map(|i| RgbaImage::from_fn(8, 8, |x, y| Rgba([x as u8 * 30, y as u8 * 30, i * 60, 255])))
+            .collect();
+        let options = EncodeOptions::default();
+
+        let encoded = encode_batch(&images, BcFormat::Bc1, BcQuality::Fast, true, &options).unwrap();
+        let mut frames = Vec::new();
+        for (image, (blocks, encoded)) in images.iter().zip(encoded) {
+            let (expected_blocks, expected) =
+                encode(image, BcFormat::Bc1, BcQuality::Fast, true, None, &options).unwrap();
+            let ((commands, drawn), (expected_commands, expected_drawn)) = (encoded.unwrap(), expected.unwrap());
+            assert_eq!(blocks, expected_blocks);
+            assert_eq!((calls(&commands), &drawn), (calls(&expected_commands), &expected_drawn));
+            frames.push((drawn, 8, 8, BcFormat::Bc1, Some(commands)));
+        }
+
+        let decoded = decode_batch(frames.clone()).unwrap();
+        for ((bytes, width, height, format, commands), batch) in frames.into_iter().zip(decoded) {
+            assert_eq!(decode(bytes, width, height, format, commands.as_deref(), None).unwrap(), batch);
+        }
+    }
+
+    #[test]
+    fn animation_batches_match_sequential_calls() {
+        let frame = |color: [u8; 4]| ProcessedFrame {
+            image: RgbaImage::from_pixel(8, 8, Rgba(color)),
+            offset_x1: 0,
+            offset_y1: 0,
+            offset_x2: 8,
+            offset_y2: 8,
+            anchor_x: 4,
+            anchor_y: 4,
+        };
+        let animations: Vec<Vec<ProcessedFrame>> = (0..3_u8)
+            .map(|i| vec![frame([i * 60, 0, 0, 255]), frame([i * 60, 100, 0, 255])])
+            .collect();
+        let options = EncodeOptions::default();
+
+        let encoded = encode_animations(&animations, BcFormat::Bc1, BcQuality::Fast, &options).unwrap();
+        let mut layers = Vec::new();
+        for (frames, encoded) in animations.iter().zip(encoded) {
+            let expected = encode_animation(frames, BcFormat::Bc1, BcQuality::Fast, &options).unwrap();
+            let frames_of = |encoded: &[(Vec<DrawCall>, Vec<u8>, bool)]| encoded.iter()
+                .map(|(commands, drawn, use_prev)| (calls(commands), drawn.clone(), *use_prev))
+                .collect::<Vec<_>>();
+            assert_eq!(frames_of(&encoded), frames_of(&expected));
+            let animation_layers = frames.iter()
+                .zip(encoded)
+                .map(|(frame, (commands, drawn, use_prev))| {
+                    SldLayer::from_encoded((commands, drawn), frame, BcFormat::Bc1, use_prev).unwrap()
+                })
+                .collect::<Vec<_>>();
+            layers.push(animation_layers);
+        }
+
+        let decoded = decode_animations(&layers).unwrap();
+        for (layers, batch) in layers.iter().zip(decoded) {
+            assert_eq!(decode_animation(layers).unwrap(), batch);
+        }
+    }
+}
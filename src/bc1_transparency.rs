@@ -1,8 +1,83 @@
-use image::{ImageBuffer, Rgba};
+use std::ops::Deref;
+use image::{ImageBuffer, Rgba, RgbaImage};
+use crate::format::{AlphaDither, BcQuality};
 
-pub fn fix_bc1_transparency(data: &mut [u8], image: &ImageBuffer<Rgba<u8>, Vec<u8>>, width: u32, height: u32) {
-    let block_width = (width + 3) / 4;
-    let block_height = (height + 3) / 4;
+/// Number of least-squares refinements of the principal axis fit, per quality
+const NORMAL_REFINEMENTS: usize = 1;
+const SLOW_REFINEMENTS: usize = 4;
+
+const BAYER_4X4: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+/// Returns a copy of the image whose alpha is dithered to either 0 or 255 around `alpha_cutoff`. Fully transparent and
+/// fully opaque pixels are kept as they are
+pub fn dither_alpha<C: Deref<Target = [u8]>>(
+    image: &ImageBuffer<Rgba<u8>, C>,
+    dither: AlphaDither,
+    alpha_cutoff: u8,
+) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let mut output = RgbaImage::from_fn(width, height, |x, y| *image.get_pixel(x, y));
+    let cutoff = alpha_cutoff as f32;
+
+    match dither {
+        // compressing applies the cutoff itself, so there is nothing to dither
+        AlphaDither::Threshold => {},
+        AlphaDither::Ordered => {
+            for (x, y, pixel) in output.enumerate_pixels_mut() {
+                // Spreads the thresholds over (0, 255) so that the middle of the pattern is the cutoff
+                let t = (BAYER_4X4[y as usize % 4][x as usize % 4] as f32 + 0.5) / 16.0;
+                let threshold = if t < 0.5 {
+                    cutoff * 2.0 * t
+                } else {
+                    cutoff + (255.0 - cutoff) * (2.0 * t - 1.0)
+                };
+                pixel[3] = match pixel[3] {
+                    0 | 255 => pixel[3],
+                    alpha if alpha as f32 >= threshold => 255,
+                    _ => 0,
+                };
+            }
+        }
+        AlphaDither::ErrorDiffusion => {
+            let mut errors = vec![0.0_f32; (width as usize + 2) * 2];
+            let row_len = width as usize + 2;
+
+            for y in 0..height {
+                let (current, next) = errors.split_at_mut(row_len);
+                for x in 0..width {
+                    let pixel = output.get_pixel_mut(x, y);
+                    if pixel[3] == 0 || pixel[3] == 255 {
+                        continue;
+                    }
+
+                    let value = pixel[3] as f32 + current[x as usize + 1];
+                    pixel[3] = if value >= cutoff { 255 } else { 0 };
+                    let error = value - pixel[3] as f32;
+
+                    current[x as usize + 2] += error * 7.0 / 16.0;
+                    next[x as usize] += error * 3.0 / 16.0;
+                    next[x as usize + 1] += error * 5.0 / 16.0;
+                    next[x as usize + 2] += error / 16.0;
+                }
+                current.copy_from_slice(next);
+                next.fill(0.0);
+            }
+        }
+    }
+
+    output
+}
+
+pub fn fix_bc1_transparency<C: Deref<Target = [u8]>>(
+    data: &mut [u8], image: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32, quality: BcQuality, alpha_cutoff: u8
+) {
+    let block_width = width.div_ceil(4);
+    let block_height = height.div_ceil(4);
 
     for block_y in 0..block_height {
         for block_x in 0..block_width {
@@ -19,7 +94,7 @@ pub fn fix_bc1_transparency(data: &mut [u8], image: &ImageBuffer<Rgba<u8>, Vec<u
 
                     if x < width && y < height {
                         let pixel = image.get_pixel(x, y);
-                        if pixel[3] < 128 { // Treat alpha < 128 as transparent (1-bit alpha)
+                        if pixel[3] < alpha_cutoff { // Treat alpha below the cutoff as transparent (1-bit alpha)
                             transparent_mask |= 1 << pixel_idx;
                             has_transparent = true;
                         } else {
@@ -47,6 +122,7 @@ pub fn fix_bc1_transparency(data: &mut [u8], image: &ImageBuffer<Rgba<u8>, Vec<u
                         width,
                         height,
                         transparent_mask,
+                        quality,
                     );
                 }
             }
@@ -54,14 +130,16 @@ pub fn fix_bc1_transparency(data: &mut [u8], image: &ImageBuffer<Rgba<u8>, Vec<u
     }
 }
 
-fn encode_bc1_block_with_alpha(
+#[allow(clippy::too_many_arguments)]
+fn encode_bc1_block_with_alpha<C: Deref<Target = [u8]>>(
     block: &mut [u8],
-    image: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    image: &ImageBuffer<Rgba<u8>, C>,
     block_x: u32,
     block_y: u32,
     width: u32,
     height: u32,
     transparent_mask: u16,
+    quality: BcQuality,
 ) {
     // Collect opaque pixels to find best color palette
     let mut colors = Vec::new();
@@ -82,30 +160,12 @@ fn encode_bc1_block_with_alpha(
         }
     }
 
-    // Find two representative colors (simple min/max approach)
-    let (color0_rgb, color1_rgb) = if colors.is_empty() {
-        ([0u8, 0, 0], [0u8, 0, 0])
-    } else if colors.len() == 1 {
-        (colors[0], colors[0])
-    } else {
-        // Find min and max to get color range
-        let mut min = colors[0];
-        let mut max = colors[0];
-
-        for color in &colors {
-            for i in 0..3 {
-                min[i] = min[i].min(color[i]);
-                max[i] = max[i].max(color[i]);
-            }
-        }
-
-        (min, max)
+    let (color0_565, color1_565) = match quality {
+        BcQuality::Fast => min_max_endpoints(&colors),
+        BcQuality::Normal => fit_endpoints(&colors, NORMAL_REFINEMENTS),
+        BcQuality::Slow => fit_endpoints(&colors, SLOW_REFINEMENTS),
     };
 
-    // Convert RGB888 to RGB565
-    let color0_565 = rgb888_to_rgb565(color0_rgb);
-    let color1_565 = rgb888_to_rgb565(color1_rgb);
-
     // Ensure color0 <= color1 for BC1 alpha mode (3-color + transparent)
     let (color0_565, color1_565) = if color0_565 > color1_565 {
         (color1_565, color0_565)
@@ -139,10 +199,8 @@ fn encode_bc1_block_with_alpha(
             let y = block_y * 4 + py;
             let pixel_idx = py * 4 + px;
 
-            let index = if x >= width || y >= height {
-                3 // Transparent for out of bounds
-            } else if (transparent_mask & (1 << pixel_idx)) != 0 {
-                3 // Transparent
+            let index = if x >= width || y >= height || (transparent_mask & (1 << pixel_idx)) != 0 {
+                3 // Transparent, including out of bounds pixels
             } else {
                 // Find closest color among color0, color1, color2
                 let pixel = image.get_pixel(x, y);
@@ -172,6 +230,156 @@ fn encode_bc1_block_with_alpha(
     block[7] = ((indices >> 24) & 0xFF) as u8;
 }
 
+/// Picks the per-channel minimum and maximum of the colors as endpoints
+fn min_max_endpoints(colors: &[[u8; 3]]) -> (u16, u16) {
+    let (color0_rgb, color1_rgb) = if colors.is_empty() {
+        ([0u8, 0, 0], [0u8, 0, 0])
+    } else if colors.len() == 1 {
+        (colors[0], colors[0])
+    } else {
+        // Find min and max to get color range
+        let mut min = colors[0];
+        let mut max = colors[0];
+
+        for color in colors {
+            for i in 0..3 {
+                min[i] = min[i].min(color[i]);
+                max[i] = max[i].max(color[i]);
+            }
+        }
+
+        (min, max)
+    };
+
+    (rgb888_to_rgb565(color0_rgb), rgb888_to_rgb565(color1_rgb))
+}
+
+/// Fits endpoints along the principal axis of the colors, then refines them by solving the least-squares problem for
+/// the current index assignment of the 3-color palette, keeping the endpoints with the lowest error
+fn fit_endpoints(colors: &[[u8; 3]], refinements: usize) -> (u16, u16) {
+    if colors.is_empty() {
+        return (0, 0);
+    }
+
+    let points: Vec<[f32; 3]> = colors.iter().map(|c| [c[0] as f32, c[1] as f32, c[2] as f32]).collect();
+    let n = points.len() as f32;
+    let mean = points.iter().fold([0.0; 3], |acc, p| [acc[0] + p[0] / n, acc[1] + p[1] / n, acc[2] + p[2] / n]);
+
+    let mut covariance = [[0.0_f32; 3]; 3];
+    for p in &points {
+        let d = [p[0] - mean[0], p[1] - mean[1], p[2] - mean[2]];
+        for i in 0..3 {
+            for j in 0..3 {
+                covariance[i][j] += d[i] * d[j];
+            }
+        }
+    }
+
+    // All colors are the same if the covariance has no trace
+    if covariance[0][0] + covariance[1][1] + covariance[2][2] < f32::EPSILON {
+        let color = quantize(mean);
+        return (color, color);
+    }
+
+    // Power iteration for the principal axis, starting from the covariance column of the channel that varies most,
+    // which unlike a fixed start can't be orthogonal to the spread of the colors
+    let channel = (0..3).max_by(|&a, &b| covariance[a][a].total_cmp(&covariance[b][b])).unwrap();
+    let mut axis = normalize(covariance[channel]);
+    for _ in 0..8 {
+        let next = [0, 1, 2].map(|i| (0..3).map(|j| covariance[i][j] * axis[j]).sum::<f32>());
+        if next.iter().all(|v| v.abs() < f32::EPSILON) {
+            break;
+        }
+        axis = normalize(next);
+    }
+
+    let (mut min_t, mut max_t) = (f32::MAX, f32::MIN);
+    for p in &points {
+        let t = (0..3).map(|i| (p[i] - mean[i]) * axis[i]).sum::<f32>();
+        min_t = min_t.min(t);
+        max_t = max_t.max(t);
+    }
+
+    let mut endpoints = (
+        quantize([0, 1, 2].map(|i| mean[i] + axis[i] * min_t)),
+        quantize([0, 1, 2].map(|i| mean[i] + axis[i] * max_t)),
+    );
+    let mut best = endpoints;
+    let mut best_error = block_error(colors, best);
+
+    for _ in 0..refinements {
+        let palette = palette(endpoints);
+
+        // Each color is approximated by w * a + (1 - w) * b, where w is 1, 0 or 1/2 for the palette entry it uses
+        let (mut aa, mut ab, mut bb) = (0.0_f32, 0.0_f32, 0.0_f32);
+        let (mut ax, mut bx) = ([0.0_f32; 3], [0.0_f32; 3]);
+        for (color, p) in colors.iter().zip(&points) {
+            let w = match closest(*color, &palette) {
+                0 => 1.0,
+                1 => 0.0,
+                _ => 0.5,
+            };
+            aa += w * w;
+            ab += w * (1.0 - w);
+            bb += (1.0 - w) * (1.0 - w);
+            for i in 0..3 {
+                ax[i] += w * p[i];
+                bx[i] += (1.0 - w) * p[i];
+            }
+        }
+
+        let det = aa * bb - ab * ab;
+        if det.abs() < f32::EPSILON {
+            break;
+        }
+
+        let next = (
+            quantize([0, 1, 2].map(|i| (ax[i] * bb - bx[i] * ab) / det)),
+            quantize([0, 1, 2].map(|i| (bx[i] * aa - ax[i] * ab) / det)),
+        );
+        if next == endpoints {
+            break;
+        }
+        endpoints = next;
+
+        let error = block_error(colors, endpoints);
+        if error < best_error {
+            (best, best_error) = (endpoints, error);
+        }
+    }
+
+    best
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let norm = v.iter().map(|v| v * v).sum::<f32>().sqrt();
+    v.map(|v| v / norm)
+}
+
+/// The colors of the 3-color palette of the endpoints, without the transparent entry
+fn palette((color0, color1): (u16, u16)) -> [[u8; 3]; 3] {
+    let color0 = rgb565_to_rgb888(color0);
+    let color1 = rgb565_to_rgb888(color1);
+    let color2 = [0, 1, 2].map(|i| ((color0[i] as u16 + color1[i] as u16) / 2) as u8);
+    [color0, color1, color2]
+}
+
+fn closest(color: [u8; 3], palette: &[[u8; 3]; 3]) -> usize {
+    (0..3).min_by_key(|&i| color_distance(color, palette[i])).unwrap()
+}
+
+fn block_error(colors: &[[u8; 3]], endpoints: (u16, u16)) -> u32 {
+    let palette = palette(endpoints);
+    colors.iter().map(|&color| color_distance(color, palette[closest(color, &palette)])).sum()
+}
+
+fn quantize(rgb: [f32; 3]) -> u16 {
+    let r = (rgb[0].clamp(0.0, 255.0) * 31.0 / 255.0).round() as u16;
+    let g = (rgb[1].clamp(0.0, 255.0) * 63.0 / 255.0).round() as u16;
+    let b = (rgb[2].clamp(0.0, 255.0) * 31.0 / 255.0).round() as u16;
+    (r << 11) | (g << 5) | b
+}
+
 fn rgb888_to_rgb565(rgb: [u8; 3]) -> u16 {
     let r = (rgb[0] as u16 >> 3) & 0x1F;
     let g = (rgb[1] as u16 >> 2) & 0x3F;
@@ -197,4 +405,51 @@ fn color_distance(a: [u8; 3], b: [u8; 3]) -> u32 {
     let dg = a[1] as i32 - b[1] as i32;
     let db = a[2] as i32 - b[2] as i32;
     (dr * dr + dg * dg + db * db) as u32
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 16 colors going linearly from `from` to `to`
+    fn gradient(from: [u8; 3], to: [u8; 3]) -> Vec<[u8; 3]> {
+        (0..16)
+            .map(|t| [0, 1, 2].map(|i| (from[i] as f32 + (to[i] as f32 - from[i] as f32) * t as f32 / 15.0) as u8))
+            .collect()
+    }
+
+    #[test]
+    fn fitted_endpoints_beat_min_max_on_gradients() {
+        let gradients = [
+            ([255, 0, 0], [0, 255, 0]),
+            ([0, 0, 255], [255, 255, 0]),
+            ([0, 255, 255], [255, 0, 255]),
+            ([0, 0, 0], [255, 255, 255]),
+            ([40, 200, 90], [200, 40, 90]),
+        ];
+
+        for (from, to) in gradients {
+            let colors = gradient(from, to);
+            let fast = block_error(&colors, min_max_endpoints(&colors));
+            for refinements in [NORMAL_REFINEMENTS, SLOW_REFINEMENTS] {
+                let fitted = block_error(&colors, fit_endpoints(&colors, refinements));
+                assert!(fitted <= fast, "{from:?} to {to:?}: fitted error {fitted} exceeds min/max error {fast}");
+            }
+        }
+    }
+
+    #[test]
+    fn fitted_endpoints_span_gradient_orthogonal_to_gray() {
+        // red to green varies orthogonally to (1, 1, 1), which must not collapse the block to a single color
+        let colors = gradient([255, 0, 0], [0, 255, 0]);
+        let (color0, color1) = fit_endpoints(&colors, SLOW_REFINEMENTS);
+        assert_ne!(color0, color1);
+    }
+
+    #[test]
+    fn fitted_endpoints_of_solid_block_are_its_color() {
+        let colors = vec![[120, 60, 30]; 16];
+        let color = quantize([120.0, 60.0, 30.0]);
+        assert_eq!(fit_endpoints(&colors, SLOW_REFINEMENTS), (color, color));
+    }
+}
@@ -0,0 +1,308 @@
+use image::{imageops, RgbaImage};
+use crate::animation::encode_animation;
+use crate::encode_decode::EncodeOptions;
+use crate::error::{Error, Result};
+use crate::format::{BcFormat, BcQuality};
+use crate::preprocess::{preprocess_layer_images, FrameLayers, ProcessedFrame};
+use crate::sld::{SldFile, SldFrame, SldLayer, SLD_VERSION};
+use crate::slp::SlpFile;
+use crate::smx::SmxFile;
+
+/// Preprocesses and encodes the layers of every frame into an SLD file. The main layer and the damage mask are BC1
+/// compressed, the shadow and player color mask are BC4 compressed, and every layer reuses the blocks of the same layer
+/// in the previous frame where possible. A layer missing from a frame is encoded from scratch in the next frame
+pub fn encode_sld(
+    frames: Vec<(FrameLayers<RgbaImage>, (i32, i32))>,
+    quality: BcQuality,
+    options: &EncodeOptions,
+) -> Result<SldFile> {
+    let (processed, (hotspot_x, hotspot_y), (canvas_width, canvas_height)) =
+        preprocess_layer_images(frames, options.alpha_cutoff)?;
+    let num_frames = processed.len();
+
+    let mut main = Vec::with_capacity(num_frames);
+    let mut shadow = Vec::with_capacity(num_frames);
+    let mut player_color = Vec::with_capacity(num_frames);
+    let mut damage_mask = Vec::with_capacity(num_frames);
+    for layers in processed {
+        main.push(Some(layers.main));
+        shadow.push(layers.shadow);
+        player_color.push(layers.player_color);
+        damage_mask.push(layers.damage_mask);
+    }
+
+    let main = encode_layer(main, BcFormat::Bc1, quality, options)?;
+    let shadow = encode_layer(shadow, BcFormat::Bc4, quality, options)?;
+    let player_color = encode_layer(player_color, BcFormat::Bc4, quality, options)?;
+    let damage_mask = encode_layer(damage_mask, BcFormat::Bc1, quality, options)?;
+
+    let to_u16 = |value: u32| u16::try_from(value)
+        .map_err(|_| Error::InvalidInput(format!("{value} does not fit in an SLD header")));
+
+    let sld_frames = main.into_iter()
+        .zip(shadow)
+        .zip(player_color)
+        .zip(damage_mask)
+        .enumerate()
+        .map(|(idx, (((main, shadow), player_color), damage_mask))| {
+            Ok(SldFrame::new(
+                to_u16(canvas_width)?,
+                to_u16(canvas_height)?,
+                to_u16(hotspot_x)?,
+                to_u16(hotspot_y)?,
+                to_u16(idx as u32)?,
+                main,
+                shadow,
+                None,
+                damage_mask,
+                player_color,
+                0,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    SldFile::new(sld_frames, SLD_VERSION)
+}
+
+/// Converts an SLP file to SLD, see `SlpFrame::decode` for the meaning of `palette` and `player`. The player color
+/// mask is derived from the player color pixels and the shadow layer from the shadow pixels, which are fully opaque.
+/// These layers are only written if some frame has such pixels. The hotspots are kept as the anchors of the frames
+pub fn slp_to_sld(
+    slp: &SlpFile,
+    palette: &[[u8; 3]],
+    player: u8,
+    quality: BcQuality,
+    options: &EncodeOptions,
+) -> Result<SldFile> {
+    let decoded = slp.decode(palette, player)?;
+    let has_shadow = decoded.iter().any(|frame| has_content(&frame.shadow));
+    let has_player_color = decoded.iter().any(|frame| has_content(&frame.player_color));
+
+    let frames = decoded.into_iter()
+        .map(|frame| {
+            let layers = FrameLayers::new(
+                frame.image,
+                has_shadow.then_some(frame.shadow),
+                has_player_color.then_some(frame.player_color),
+                None,
+            );
+            (layers, frame.hotspot)
+        })
+        .collect();
+
+    encode_sld(frames, quality, options)
+}
+
+/// Converts an SMX file to SLD, see `SmxFrame::decode` for the meaning of `palette` and `player_palette`. The shadow
+/// layer keeps the opacity of the SMX shadow and is aligned with the main layer through the hotspots, the player color
+/// mask is derived from the player color pixels. These layers are only written if some frame has such pixels
+pub fn smx_to_sld(
+    smx: &SmxFile,
+    palette: &[[u8; 3]],
+    player_palette: Option<&[[u8; 3]]>,
+    quality: BcQuality,
+    options: &EncodeOptions,
+) -> Result<SldFile> {
+    let decoded = smx.decode(palette, player_palette)?;
+    let has_shadow = decoded.iter().any(|frame| has_content(&frame.shadow));
+    let has_player_color = decoded.iter().any(|frame| has_content(&frame.player_color));
+
+    let frames = decoded.into_iter()
+        .map(|frame| {
+            // the layers of an SMX frame have their own hotspots, so they are moved onto an area that fits all of them
+            let anchor = (frame.hotspot.0.max(frame.shadow_hotspot.0), frame.hotspot.1.max(frame.shadow_hotspot.1));
+            let width = (frame.image.width() as i32 - frame.hotspot.0)
+                .max(frame.shadow.width() as i32 - frame.shadow_hotspot.0) + anchor.0;
+            let height = (frame.image.height() as i32 - frame.hotspot.1)
+                .max(frame.shadow.height() as i32 - frame.shadow_hotspot.1) + anchor.1;
+            let align = |image: &RgbaImage, hotspot: (i32, i32)| {
+                let mut aligned = RgbaImage::new(width as u32, height as u32);
+                imageops::replace(&mut aligned, image, (anchor.0 - hotspot.0) as i64, (anchor.1 - hotspot.1) as i64);
+                aligned
+            };
+
+            let layers = FrameLayers::new(
+                align(&frame.image, frame.hotspot),
+                has_shadow.then(|| align(&frame.shadow, frame.shadow_hotspot)),
+                has_player_color.then(|| align(&frame.player_color, frame.hotspot)),
+                None,
+            );
+            (layers, anchor)
+        })
+        .collect();
+
+    encode_sld(frames, quality, options)
+}
+
+/// Encodes every run of consecutive frames that have this layer as an animation
+fn encode_layer(
+    frames: Vec<Option<ProcessedFrame>>,
+    format: BcFormat,
+    quality: BcQuality,
+    options: &EncodeOptions,
+) -> Result<Vec<Option<SldLayer>>> {
+    let encode_run = |run: &[ProcessedFrame]| {
+        run.iter()
+            .zip(encode_animation(run, format, quality, options)?)
+            .map(|(frame, (commands, blocks, use_prev))| {
+                SldLayer::from_encoded((commands, blocks), frame, format, use_prev).map(Some)
+            })
+            .collect::<Result<Vec<_>>>()
+    };
+
+    let mut layers = Vec::with_capacity(frames.len());
+    let mut run = Vec::new();
+    for frame in frames {
+        match frame {
+            Some(frame) => run.push(frame),
+            None => {
+                layers.extend(encode_run(&run)?);
+                layers.push(None);
+                run.clear();
+            },
+        }
+    }
+    layers.extend(encode_run(&run)?);
+
+    Ok(layers)
+}
+
+/// Whether a mask has any pixels set, masks store their value in the red channel
+fn has_content(mask: &RgbaImage) -> bool {
+    mask.pixels().any(|pixel| pixel[0] != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sld::{FLAG_BC4, LAYER_MAIN, LAYER_PLAYER_COLOR, LAYER_SHADOW};
+    use crate::slp::{SlpFrame, SlpHeader, SlpPixel};
+    use crate::smx::{SmxFrame, SmxHeader, SmxLayer, SmxPixel};
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    /// A palette whose color 1 is red and whose color 16, the first color of player 1, is blue
+    fn palette() -> Vec<[u8; 3]> {
+        let mut palette = vec![[0; 3]; 32];
+        palette[1] = [255, 0, 0];
+        palette[16] = [0, 0, 255];
+        palette
+    }
+
+    /// Writes the file and reads it back, then decodes the given layers of its only frame, each paired with its
+    /// offsets and flag
+    #[allow(clippy::type_complexity)]
+    fn read_back(sld: &SldFile, kinds: &[u8]) -> Vec<(u8, (u16, u16, u16, u16), u8, RgbaImage)> {
+        let sld = SldFile::from_bytes(&sld.to_bytes().unwrap()).unwrap();
+        assert_eq!(sld.frames.len(), 1);
+        let frame = &sld.frames[0];
+        assert_eq!(frame.frame_type, kinds.iter().fold(0, |frame_type, kind| frame_type | kind));
+
+        frame.layers().into_iter()
+            .map(|(kind, layer)| {
+                let header = &layer.header;
+                let offsets = (header.offset_x1, header.offset_y1, header.offset_x2, header.offset_y2);
+                (kind, offsets, header.flag1, layer.decode(None).unwrap().0)
+            })
+            .collect()
+    }
+
+    /// Checks the red channel of every pixel in the rectangle, or the whole pixel if `color` has 4 channels
+    fn assert_area(image: &RgbaImage, (x1, y1, x2, y2): (u32, u32, u32, u32), color: &[u8]) {
+        for y in y1..y2 {
+            for x in x1..x2 {
+                assert_eq!(&image.get_pixel(x, y).0[..color.len()], color, "pixel ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn converts_slp() {
+        // an 8x8 frame anchored at its center, with a red and a player color square above a shadow square
+        let pixels = (0..64)
+            .map(|idx| match (idx % 8 < 4, idx / 8 < 4) {
+                (true, true) => SlpPixel::Color(1),
+                (false, true) => SlpPixel::PlayerColor(0),
+                (true, false) => SlpPixel::Shadow,
+                (false, false) => SlpPixel::Transparent,
+            })
+            .collect();
+        let slp = SlpFile {
+            header: SlpHeader { version: "2.0N".to_string(), num_frames: 1, comment: String::new() },
+            frames: vec![SlpFrame {
+                width: 8, height: 8, hotspot_x: 4, hotspot_y: 4, palette_offset: 0, properties: 0, pixels,
+            }],
+        };
+
+        let sld = slp_to_sld(&slp, &palette(), 1, BcQuality::Fast, &EncodeOptions::default()).unwrap();
+        assert_eq!(sld.frames[0].canvas_width, 8);
+        assert_eq!(sld.frames[0].canvas_height, 8);
+        assert_eq!((sld.frames[0].canvas_hotspot_x, sld.frames[0].canvas_hotspot_y), (4, 4));
+
+        let layers = read_back(&sld, &[LAYER_MAIN, LAYER_SHADOW, LAYER_PLAYER_COLOR]);
+        let [(_, main_offsets, main_flag, main), (_, shadow_offsets, shadow_flag, shadow),
+            (_, player_offsets, player_flag, player)] = &layers[..] else { panic!("expected 3 layers") };
+
+        assert_eq!(*main_offsets, (0, 0, 8, 4));
+        assert_eq!(*main_flag, 0);
+        assert_area(main, (0, 0, 4, 4), &RED);
+        assert_area(main, (4, 0, 8, 4), &BLUE);
+
+        assert_eq!(*shadow_offsets, (0, 4, 4, 8));
+        assert_eq!(*shadow_flag, FLAG_BC4);
+        assert_area(shadow, (0, 0, 4, 4), &[255]);
+
+        assert_eq!(*player_offsets, (0, 0, 8, 4));
+        assert_eq!(*player_flag, FLAG_BC4);
+        assert_area(player, (0, 0, 4, 4), &[0]);
+        assert_area(player, (4, 0, 8, 4), &[255]);
+    }
+
+    #[test]
+    fn converts_smx() {
+        // an 8x4 main layer with a red and a player color square above the unit's position and a 4x4 shadow whose
+        // hotspot puts it at the bottom right of that position, so that the layers are aligned on a canvas of 8x8
+        let main = SmxLayer {
+            width: 8, height: 4, hotspot_x: 4, hotspot_y: 4, unknown: 0,
+            pixels: (0..32)
+                .map(|idx| if idx % 8 < 4 { SmxPixel::Color(1) } else { SmxPixel::PlayerColor(16) })
+                .collect(),
+        };
+        let shadow = SmxLayer {
+            width: 4, height: 4, hotspot_x: 0, hotspot_y: 0, unknown: 0, pixels: vec![SmxPixel::Shadow(128); 16],
+        };
+        let smx = SmxFile {
+            header: SmxHeader {
+                version: 2, num_frames: 1, file_size_comp: 0, file_size_uncomp: 0, comment: String::new(),
+            },
+            frames: vec![SmxFrame {
+                frame_type: 0, palette_number: 0, uncomp_size: 0,
+                main_layer: Some(main), shadow_layer: Some(shadow), outline_layer: None,
+            }],
+        };
+
+        let sld = smx_to_sld(&smx, &palette(), None, BcQuality::Fast, &EncodeOptions::default()).unwrap();
+        assert_eq!(sld.frames[0].canvas_width, 8);
+        assert_eq!(sld.frames[0].canvas_height, 8);
+        assert_eq!((sld.frames[0].canvas_hotspot_x, sld.frames[0].canvas_hotspot_y), (4, 4));
+
+        let layers = read_back(&sld, &[LAYER_MAIN, LAYER_SHADOW, LAYER_PLAYER_COLOR]);
+        let [(_, main_offsets, main_flag, main), (_, shadow_offsets, shadow_flag, shadow),
+            (_, player_offsets, player_flag, player)] = &layers[..] else { panic!("expected 3 layers") };
+
+        assert_eq!(*main_offsets, (0, 0, 8, 4));
+        assert_eq!(*main_flag, 0);
+        assert_area(main, (0, 0, 4, 4), &RED);
+        assert_area(main, (4, 0, 8, 4), &BLUE);
+
+        assert_eq!(*shadow_offsets, (4, 4, 8, 8));
+        assert_eq!(*shadow_flag, FLAG_BC4);
+        assert_area(shadow, (0, 0, 4, 4), &[128]);
+
+        assert_eq!(*player_offsets, (0, 0, 8, 4));
+        assert_eq!(*player_flag, FLAG_BC4);
+        assert_area(player, (0, 0, 4, 4), &[0]);
+        assert_area(player, (4, 0, 8, 4), &[255]);
+    }
+}
@@ -0,0 +1,122 @@
+use std::ops::Deref;
+use image::imageops::{self, FilterType};
+use image::{ImageBuffer, Rgba, RgbaImage};
+use image_dds::ddsfile::{AlphaMode, D3D10ResourceDimension, D3DFormat, Dds, DxgiFormat, NewD3dParams, NewDxgiParams};
+use crate::encode_decode::{bc_format, decode, encode, EncodeOptions};
+use crate::error::{Error, Result};
+use crate::format::{BcFormat, BcQuality};
+
+/// Wraps compressed blocks, e.g. those returned by `encode`, in a DDS file. `blocks` holds `mipmaps` levels one after
+/// another, each half the size of the previous one. BC1 uses the legacy DXT1 header that every DDS reader supports,
+/// BC4 and BC7 have no legacy format and use the DX10 header
+pub fn to_dds(blocks: &[u8], width: u32, height: u32, format: BcFormat, mipmaps: u32) -> Result<Vec<u8>> {
+    let mipmap_levels = Some(mipmaps.max(1));
+    let dds = match format {
+        BcFormat::Bc1 => Dds::new_d3d(NewD3dParams {
+            height,
+            width,
+            depth: None,
+            format: D3DFormat::DXT1,
+            mipmap_levels,
+            caps2: None,
+        }),
+        BcFormat::Bc4 | BcFormat::Bc7 => Dds::new_dxgi(NewDxgiParams {
+            height,
+            width,
+            depth: None,
+            format: if format == BcFormat::Bc4 { DxgiFormat::BC4_UNorm } else { DxgiFormat::BC7_UNorm },
+            mipmap_levels,
+            array_layers: None,
+            caps2: None,
+            is_cubemap: false,
+            resource_dimension: D3D10ResourceDimension::Texture2D,
+            alpha_mode: AlphaMode::Straight,
+        }),
+    };
+    let mut dds = dds.map_err(|e| Error::InvalidInput(format!("Failed to create a DDS header: {e}")))?;
+
+    if blocks.len() != dds.data.len() {
+        return Err(Error::InvalidInput(format!(
+            "Block data ({} bytes) does not match {width}x{height} pixels with {} mipmaps in {format:?}, which need {} \
+            bytes", blocks.len(), mipmaps.max(1), dds.data.len()
+        )));
+    }
+    dds.data.copy_from_slice(blocks);
+
+    let mut bytes = Vec::new();
+    dds.write(&mut bytes).map_err(|e| Error::InvalidInput(format!("Failed to write the DDS file: {e}")))?;
+    Ok(bytes)
+}
+
+/// Compresses the image into a DDS file, see `to_dds`. If `mipmaps` is set, the full chain of mipmaps down to 1x1 is
+/// generated by downscaling the image and every level is compressed the same way as the image
+pub fn encode_dds<C: Deref<Target = [u8]>>(
+    image: &ImageBuffer<Rgba<u8>, C>,
+    format: BcFormat,
+    quality: BcQuality,
+    mipmaps: bool,
+    options: &EncodeOptions,
+) -> Result<Vec<u8>> {
+    let (width, height) = image.dimensions();
+    let levels = if mipmaps { u32::BITS - width.max(height).max(1).leading_zeros() } else { 1 };
+
+    let (mut blocks, _) = encode(image, format, quality, false, None, options)?;
+    for level in 1..levels {
+        let mipmap = imageops::resize(image, (width >> level).max(1), (height >> level).max(1), FilterType::Triangle);
+        blocks.extend(encode(&mipmap, format, quality, false, None, options)?.0);
+    }
+
+    to_dds(&blocks, width, height, format, levels)
+}
+
+/// Reads a BC1, BC4 or BC7 DDS file and decompresses its first mipmap. Returns the image, its format and its
+/// compressed blocks
+pub fn decode_dds(bytes: &[u8]) -> Result<(RgbaImage, BcFormat, Vec<u8>)> {
+    let dds = Dds::read(bytes).map_err(|e| Error::Parse(format!("Failed to read the DDS file: {e}")))?;
+    let image_format = image_dds::dds_image_format(&dds)
+        .map_err(|e| Error::Parse(format!("Unsupported DDS format: {e:?}")))?;
+    let format = bc_format(image_format).ok_or_else(|| Error::Parse(format!(
+        "Unsupported DDS format {image_format:?}, only BC1, BC4 and BC7 are supported"
+    )))?;
+
+    let (width, height) = (dds.get_width(), dds.get_height());
+    let len = width.div_ceil(4) as usize * height.div_ceil(4) as usize * format.block_size();
+    if dds.data.len() < len {
+        return Err(Error::Parse(format!(
+            "The DDS file has {} bytes of block data but {width}x{height} pixels in {format:?} need {len} bytes",
+            dds.data.len()
+        )));
+    }
+
+    let (image, blocks) = decode(dds.data[..len].to_vec(), width, height, format, None, None)?;
+    Ok((image, format, blocks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_every_format() {
+        let image = RgbaImage::from_fn(8, 8, |x, y| Rgba([x as u8 * 30, y as u8 * 30, 100, 255]));
+        let options = EncodeOptions::default();
+
+        for (format, four_cc) in [(BcFormat::Bc1, b"DXT1"), (BcFormat::Bc4, b"DX10"), (BcFormat::Bc7, b"DX10")] {
+            let bytes = encode_dds(&image, format, BcQuality::Fast, true, &options).unwrap();
+            assert_eq!(&bytes[84..88], four_cc);
+            // 8x8, 4x4, 2x2 and 1x1
+            assert_eq!(u32::from_le_bytes(bytes[28..32].try_into().unwrap()), 4);
+
+            let (expected, _) = encode(&image, format, BcQuality::Fast, false, None, &options).unwrap();
+            let (decoded, decoded_format, blocks) = decode_dds(&bytes).unwrap();
+            assert_eq!(decoded_format, format);
+            assert_eq!(blocks, expected);
+            assert_eq!(decoded, decode(expected, 8, 8, format, None, None).unwrap().0);
+        }
+    }
+
+    #[test]
+    fn rejects_blocks_of_the_wrong_size() {
+        assert!(to_dds(&[0; 8], 8, 8, BcFormat::Bc1, 1).is_err());
+    }
+}
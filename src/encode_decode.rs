@@ -1,110 +1,310 @@
+use std::ops::Deref;
 use std::ptr;
-use image::{ImageBuffer, Rgba};
+use image::{ImageBuffer, Rgba, RgbaImage};
 use image_dds::{ImageFormat, Mipmaps, Quality, Surface};
-use pyo3::{pyfunction, PyResult};
-use pyo3::exceptions::PyValueError;
-use crate::bc1_transparency::fix_bc1_transparency;
-use crate::format::{BcFormat, BcQuality, DrawCall};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use crate::bc1_transparency::{dither_alpha, fix_bc1_transparency};
+use crate::error::{Error, Result};
+use crate::format::{AlphaDither, BcFormat, BcQuality, DrawCall, PrevInfo, SkipMode, DEFAULT_ALPHA_CUTOFF};
 use crate::helper;
+use crate::preprocess::bleed_colors;
 
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EncodeOptions {
+    /// How blocks are compared with the previous frame to decide whether they are skipped
+    pub skip_mode: SkipMode,
+    /// Also skips blocks whose source pixels differ by at most this much in every channel from the previous frame's
+    /// decoded block
+    pub max_delta: Option<u8>,
+    /// Also skips blocks whose source pixels have at most this mean squared error from the previous frame's decoded
+    /// block. If both `max_delta` and `max_mse` are set, a block must be within both
+    pub max_mse: Option<f32>,
+    /// BC1 pixels with a lower alpha are transparent, this should match the cutoff given to `preprocess_frames`
+    pub alpha_cutoff: u8,
+    /// How semi-transparent pixels are turned into BC1's 1-bit alpha
+    pub alpha_dither: AlphaDither,
+    /// Fills transparent pixels with the colors of nearby opaque pixels before compressing. Only changes BC7 blocks,
+    /// see `bleed_colors`
+    pub bleed_colors: bool,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            skip_mode: SkipMode::Bitwise,
+            max_delta: None,
+            max_mse: None,
+            alpha_cutoff: DEFAULT_ALPHA_CUTOFF,
+            alpha_dither: AlphaDither::Threshold,
+            bleed_colors: false,
+        }
+    }
+}
+
+impl EncodeOptions {
+    fn is_lossy(&self) -> bool {
+        self.max_delta.is_some() || self.max_mse.is_some()
+    }
+}
+
+/// Applies the alpha dithering and color bleeding of the options, as `encode` does before compressing. Returns the
+/// image to compress if it differs from the given one, along with the options that compress it as it is
+pub(crate) fn prepare_image<C: Deref<Target = [u8]>>(
+    image: &ImageBuffer<Rgba<u8>, C>,
+    format: BcFormat,
+    options: &EncodeOptions,
+) -> (Option<RgbaImage>, EncodeOptions) {
+    let dither = format == BcFormat::Bc1 && options.alpha_dither != AlphaDither::Threshold;
+    let bleed = format == BcFormat::Bc7 && options.bleed_colors;
+    if !dither && !bleed {
+        return (None, *options);
+    }
+
+    let mut image = if dither {
+        dither_alpha(image, options.alpha_dither, options.alpha_cutoff)
+    } else {
+        RgbaImage::from_raw(image.width(), image.height(), image.as_raw().to_vec()).unwrap()
+    };
+    if bleed {
+        bleed_colors(&mut image, format, options.alpha_cutoff);
+    }
+
+    (Some(image), EncodeOptions { alpha_dither: AlphaDither::Threshold, bleed_colors: false, ..*options })
+}
+
+/// Compresses the image, returning the compressed blocks and, if `gen_commands` is set, the skip/draw commands along
+/// with only the blocks that are drawn, for use in SLD files. The image may borrow its pixels, e.g. from a raw buffer
 #[allow(clippy::type_complexity)]
-#[pyfunction]
-#[pyo3(signature = (
-    bytes, format = BcFormat::Bc1, quality = BcQuality::Slow, gen_commands = false, prev_info = None
-))]
-pub fn encode(
-    bytes: &[u8],
+pub fn encode<C: Deref<Target = [u8]>>(
+    image: &ImageBuffer<Rgba<u8>, C>,
     format: BcFormat,
     quality: BcQuality,
     gen_commands: bool,
-    prev_info: Option<(&[u8], u32, u32, i32, i32)>,
-) -> PyResult<(Vec<u8>, Option<(Vec<DrawCall>, Vec<u8>)>)> {
-    let image = image::load_from_memory(bytes)
-        .map_err(|e| PyValueError::new_err(format!("Failed to load image: {}", e)))?
-        .to_rgba8();
-
-    let mut surface = image_dds::SurfaceRgba8::from_image(&image).encode(
-        match format {
-            BcFormat::Bc1 => ImageFormat::BC1RgbaUnorm,
-            BcFormat::Bc4 => ImageFormat::BC4RUnorm,
-            BcFormat::Bc7 => ImageFormat::BC7RgbaUnorm,
-        },
+    prev_info: Option<PrevInfo>,
+    options: &EncodeOptions,
+) -> Result<(Vec<u8>, Option<(Vec<DrawCall>, Vec<u8>)>)> {
+    if let (Some(image), options) = prepare_image(image, format, options) {
+        return encode(&image, format, quality, gen_commands, prev_info, &options);
+    }
+
+    let surface = image_dds::SurfaceRgba8 {
+        width: image.width(),
+        height: image.height(),
+        depth: 1,
+        layers: 1,
+        mipmaps: 1,
+        data: &**image.as_raw(),
+    };
+
+    let mut surface = surface.encode(
+        image_format(format),
         match quality {
             BcQuality::Fast => Quality::Fast,
             BcQuality::Normal => Quality::Normal,
             BcQuality::Slow => Quality::Slow,
         },
         Mipmaps::Disabled,
-    ).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    )?;
 
     if matches!(format, BcFormat::Bc1) {
-        fix_bc1_transparency(&mut surface.data, &image, surface.width, surface.height);
+        fix_bc1_transparency(&mut surface.data, image, surface.width, surface.height, quality, options.alpha_cutoff);
     }
+    helper::canonicalize_transparent_blocks(&mut surface.data, image, format, options.alpha_cutoff);
     
     if !gen_commands {
         return Ok((surface.data, None));
     }
-    
-    let (width, height) = (surface.width, surface.height);
 
-    let block_width = (width + 3) / 4;
-    let block_height = (height + 3) / 4;
+    if let Some(prev_info) = prev_info {
+        reuse_prev_blocks(&mut surface.data, image, format, prev_info, options)?;
+    }
+    let encoded_data = generate_commands(&surface.data, surface.width, surface.height, format, prev_info);
+
+    Ok((surface.data, Some(encoded_data)))
+}
+
+/// Generates the skip/draw commands for already compressed blocks, returning the commands and the drawn blocks
+pub fn generate_commands(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    format: BcFormat,
+    prev_info: Option<PrevInfo>,
+) -> (Vec<DrawCall>, Vec<u8>) {
+    let block_width = width.div_ceil(4);
+    let block_height = height.div_ceil(4);
 
     let total_blocks = (block_width * block_height) as usize;
+    let transparent_block = format.transparent_block();
 
-    let (_block_size, transparent_block) = match format {
-        BcFormat::Bc1 => (8, vec![0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]),
-        BcFormat::Bc4 => (8, vec![0_u8; 8]),
+    match format {
+        BcFormat::Bc1 | BcFormat::Bc4 => {
+            helper::encode_blocks::<u64>(data, block_width, total_blocks, &transparent_block, prev_info)
+        }
         BcFormat::Bc7 => {
-            let mut block = vec![0_u8; 16];
-            block[0] = 0x40;
-            (16, block)
-        },
+            helper::encode_blocks::<u128>(data, block_width, total_blocks, &transparent_block, prev_info)
+        }
+    }
+}
+
+/// Replaces the blocks that `options` considers equal to the block at the same position in the previous frame with
+/// that block, so that they are skipped and the returned blocks match what a decoder reconstructs
+pub fn reuse_prev_blocks<C: Deref<Target = [u8]>>(
+    data: &mut [u8],
+    image: &ImageBuffer<Rgba<u8>, C>,
+    format: BcFormat,
+    prev_info: PrevInfo,
+    options: &EncodeOptions,
+) -> Result<()> {
+    if options.skip_mode == SkipMode::Bitwise && !options.is_lossy() {
+        return Ok(());
+    }
+
+    let (prev, prev_width, prev_height, off_x, off_y) = prev_info;
+    let (block_width, block_height) = (image.width().div_ceil(4), image.height().div_ceil(4));
+    let (prev_block_width, prev_block_height) = (prev_width.div_ceil(4), prev_height.div_ceil(4));
+    let (off_x, off_y) = (off_x / 4, off_y / 4);
+    let block_size = format.block_size();
+
+    if prev.len() < (prev_block_width * prev_block_height) as usize * block_size {
+        return Err(Error::InvalidInput(format!(
+            "Previous block data ({} bytes) is too small for the specified dimensions ({prev_width}x{prev_height})",
+            prev.len()
+        )));
+    }
+
+    // edge blocks are decoded whole so that every block covers 4x4 pixels
+    let pixels = match options.skip_mode {
+        SkipMode::Bitwise => Vec::new(),
+        SkipMode::Pixels => decode_blocks(data, block_width * 4, block_height * 4, format)?,
     };
+    let prev_pixels = decode_blocks(prev, prev_block_width * 4, prev_block_height * 4, format)?;
 
-    let encoded_data = match format {
-        BcFormat::Bc1 | BcFormat::Bc4 => {
-            helper::encode_blocks::<u64>(&surface.data, block_width, total_blocks, &transparent_block, prev_info)
+    for (idx, block) in data.chunks_exact_mut(block_size).enumerate() {
+        let (x, y) = ((idx as u32 % block_width) as i32, (idx as u32 / block_width) as i32);
+        let (prev_x, prev_y) = (x - off_x, y - off_y);
+        if prev_x < 0 || prev_y < 0 || prev_x >= prev_block_width as i32 || prev_y >= prev_block_height as i32 {
+            continue;
         }
-        BcFormat::Bc7 => {
-            helper::encode_blocks::<u128>(&surface.data, block_width, total_blocks, &transparent_block, prev_info)
+
+        let prev_idx = (prev_y as u32 * prev_block_width + prev_x as u32) as usize;
+        let prev_block = &prev[prev_idx * block_size..(prev_idx + 1) * block_size];
+        if block == prev_block {
+            continue;
         }
+
+        let prev_row_start = |row: usize| {
+            ((prev_y as usize * 4 + row) * prev_block_width as usize * 4 + prev_x as usize * 4) * 4
+        };
+
+        let same_pixels = options.skip_mode == SkipMode::Pixels && (0..4).all(|row| {
+            let start = ((y as usize * 4 + row) * block_width as usize * 4 + x as usize * 4) * 4;
+            pixels[start..start + 16] == prev_pixels[prev_row_start(row)..prev_row_start(row) + 16]
+        });
+
+        let within_error = options.is_lossy() && {
+            let (mut max_delta, mut squared_error, mut count) = (0_u8, 0_u32, 0_u32);
+            let mut visibility_matches = true;
+
+            for row in 0..4 {
+                for col in 0..4 {
+                    let (src_x, src_y) = (x as u32 * 4 + col, y as u32 * 4 + row);
+                    if src_x >= image.width() || src_y >= image.height() {
+                        continue;
+                    }
+                    let source = image.get_pixel(src_x, src_y);
+                    let start = prev_row_start(row as usize) + col as usize * 4;
+                    let decoded = Rgba([
+                        prev_pixels[start], prev_pixels[start + 1], prev_pixels[start + 2], prev_pixels[start + 3]
+                    ]);
+
+                    let Some(channels) = compared_channels(format, source, &decoded, options.alpha_cutoff) else {
+                        visibility_matches = false;
+                        continue;
+                    };
+                    for &channel in channels {
+                        let delta = source[channel].abs_diff(decoded[channel]);
+                        max_delta = max_delta.max(delta);
+                        squared_error += delta as u32 * delta as u32;
+                        count += 1;
+                    }
+                }
+            }
+
+            visibility_matches
+                && options.max_delta.is_none_or(|max| max_delta <= max)
+                && options.max_mse.is_none_or(|max| count == 0 || squared_error as f32 / count as f32 <= max)
+        };
+
+        if same_pixels || within_error {
+            block.copy_from_slice(prev_block);
+        }
+    }
+
+    Ok(())
+}
+
+/// The channels of a source pixel and a decoded pixel that the format stores and that are visible, or `None` if only
+/// one of the pixels is visible
+fn compared_channels(
+    format: BcFormat,
+    source: &Rgba<u8>,
+    decoded: &Rgba<u8>,
+    alpha_cutoff: u8,
+) -> Option<&'static [usize]> {
+    match format {
+        BcFormat::Bc4 => Some(&[0]),
+        BcFormat::Bc1 | BcFormat::Bc7 => {
+            match (format.is_transparent(source, alpha_cutoff), format.is_transparent(decoded, alpha_cutoff)) {
+                (true, true) => Some(&[]),
+                (false, false) if format == BcFormat::Bc1 => Some(&[0, 1, 2]),
+                (false, false) => Some(&[0, 1, 2, 3]),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Decompresses blocks into RGBA pixels
+fn decode_blocks(data: &[u8], width: u32, height: u32, format: BcFormat) -> Result<Vec<u8>> {
+    let surface = Surface {
+        width,
+        height,
+        depth: 1,
+        layers: 1,
+        mipmaps: 1,
+        image_format: image_format(format),
+        data,
     };
-    
-    Ok((surface.data, Some(encoded_data)))
+
+    Ok(surface.decode_rgba8()?.data)
 }
 
-#[pyfunction]
-#[pyo3(signature = (
-    bytes, width, height, format, commands = None, prev_info = None
-))]
+/// Decompresses blocks into an image. If `commands` are given, `bytes` only contains the drawn blocks and skipped
+/// blocks are transparent or, if `prev_info` is given, copied from the previous frame. Returns the image and the
+/// compressed blocks of this frame after the draw commands have been processed
 pub fn decode(
     bytes: Vec<u8>,
     width: u32,
     height: u32,
     format: BcFormat,
-    commands: Option<Vec<DrawCall>>,
-    prev_info: Option<(&[u8], u32, u32, i32, i32)>,
-) -> PyResult<(Vec<u8>, Vec<u8>)> {
+    commands: Option<&[DrawCall]>,
+    prev_info: Option<PrevInfo>,
+) -> Result<(RgbaImage, Vec<u8>)> {
     let data = if let Some(commands) = commands {
-        let block_width = (width + 3) / 4;
-        let block_height = (height + 3) / 4;
+        let block_width = width.div_ceil(4);
+        let block_height = height.div_ceil(4);
 
         let prev_info = prev_info.map(|(blocks, w, h, ox, oy)| {
-            (blocks, (w + 3)/4, (h + 3)/4, ox/4, oy/4)
+            (blocks, w.div_ceil(4), h.div_ceil(4), ox/4, oy/4)
         });
         
         let total_blocks = (block_width * block_height) as usize;
 
-        let (block_size, transparent_block) = match format {
-            BcFormat::Bc1 => (8, vec![0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]),
-            BcFormat::Bc4 => (8, vec![0_u8; 8]),
-            BcFormat::Bc7 => {
-                let mut block = vec![0_u8; 16];
-                block[0] = 0x40;
-                (16, block)
-            },
-        };
+        let block_size = format.block_size();
+        let transparent_block = format.transparent_block();
 
         let full_compressed_size = total_blocks * block_size;
         let mut full_compressed: Vec<u8> = Vec::with_capacity(full_compressed_size);
@@ -116,7 +316,7 @@ pub fn decode(
             let start = full_compressed.len();
             let len = cmd.skip as usize * block_size;
             if start + len > full_compressed.capacity() {
-                return Err(PyValueError::new_err(format!(
+                return Err(Error::InvalidInput(format!(
                     "Too many skip/draw calls ({} calls) for the specified dimensions ({width}x{height})",
                     (start + len)/8
                 )))
@@ -134,7 +334,7 @@ pub fn decode(
                         if pos + block_size <= prev_blocks.len() {
                             full_compressed.extend_from_slice(&prev_blocks[pos..pos + block_size]);
                         } else {
-                            return Err(PyValueError::new_err(format!(
+                            return Err(Error::InvalidInput(format!(
                                 "Previous block data ({} bytes) is too small for the specified dimensions ({prev_width}x{prev_height})",
                                 prev_blocks.len()
                             )))
@@ -157,7 +357,7 @@ pub fn decode(
 
             let len = cmd.draw as usize * block_size;
             if off+len > bytes.len() {
-                return Err(PyValueError::new_err(format!(
+                return Err(Error::InvalidInput(format!(
                     "Block data ({} bytes) is too small for the specified dimensions ({width}x{height})",
                     bytes.len()
                 )))
@@ -172,33 +372,117 @@ pub fn decode(
         bytes
     };
 
-    let surface = Surface {
-        width,
-        height,
-        depth: 1,
-        layers: 1,
-        mipmaps: 1,
-        image_format: match format {
-            BcFormat::Bc1 => ImageFormat::BC1RgbaUnorm,
-            BcFormat::Bc4 => ImageFormat::BC4RUnorm,
-            BcFormat::Bc7 => ImageFormat::BC7RgbaUnorm,
-        },
-        data: data.clone(),
-    };
+    let rgba_data = decode_blocks(&data, width, height, format)?;
 
-    let rgba_data = surface.decode_rgba8()
-        .map_err(|e| PyValueError::new_err(e.to_string()))?
-        .data;
+    let image = RgbaImage::from_raw(width, height, rgba_data)
+        .ok_or_else(|| Error::InvalidInput("Failed to create image buffer".to_string()))?;
 
-    let image_buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(
-        width,
-        height,
-        rgba_data,
-    ).ok_or_else(|| PyValueError::new_err("Failed to create image buffer"))?;
+    Ok((image, data))
+}
+
+pub fn image_format(format: BcFormat) -> ImageFormat {
+    match format {
+        BcFormat::Bc1 => ImageFormat::BC1RgbaUnorm,
+        BcFormat::Bc4 => ImageFormat::BC4RUnorm,
+        BcFormat::Bc7 => ImageFormat::BC7RgbaUnorm,
+    }
+}
+
+/// The inverse of `image_format`, the sRGB variants have the same blocks and decode the same way
+pub fn bc_format(format: ImageFormat) -> Option<BcFormat> {
+    match format {
+        ImageFormat::BC1RgbaUnorm | ImageFormat::BC1RgbaUnormSrgb => Some(BcFormat::Bc1),
+        ImageFormat::BC4RUnorm => Some(BcFormat::Bc4),
+        ImageFormat::BC7RgbaUnorm | ImageFormat::BC7RgbaUnormSrgb => Some(BcFormat::Bc7),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes the second of two 8x8 frames that differ only in their bottom right block against the first one, then
+    /// decodes it from its draw commands
+    fn round_trip(skip_mode: SkipMode) {
+        let first = RgbaImage::from_fn(8, 8, |x, y| Rgba([x as u8 * 30, y as u8 * 30, 100, 255]));
+        let mut second = first.clone();
+        second.put_pixel(5, 6, Rgba([255, 0, 255, 255]));
+        let options = EncodeOptions { skip_mode, ..Default::default() };
+
+        let (prev, _) = encode(&first, BcFormat::Bc1, BcQuality::Fast, false, None, &options).unwrap();
+        let prev_info = Some((prev.as_slice(), 8, 8, 0, 0));
+        let (blocks, encoded) = encode(&second, BcFormat::Bc1, BcQuality::Fast, true, prev_info, &options).unwrap();
+        let (commands, drawn) = encoded.unwrap();
+        assert_eq!(drawn.len(), BcFormat::Bc1.block_size());
 
-    let mut png_bytes = Vec::new();
-    image_buffer.write_to(&mut std::io::Cursor::new(&mut png_bytes), image::ImageFormat::Png)
-        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let (image, decoded) = decode(drawn, 8, 8, BcFormat::Bc1, Some(&commands), prev_info).unwrap();
+        assert_eq!(decoded, blocks);
+        assert_eq!(image, decode(blocks, 8, 8, BcFormat::Bc1, None, None).unwrap().0);
+    }
+
+    #[test]
+    fn round_trips_bitwise_skips() {
+        round_trip(SkipMode::Bitwise);
+    }
 
-    Ok((png_bytes, data))
-}
\ No newline at end of file
+    #[test]
+    fn round_trips_pixel_skips() {
+        round_trip(SkipMode::Pixels);
+    }
+
+    #[test]
+    fn pixel_skips_reuse_blocks_that_decode_the_same() {
+        // red in both blocks, with the second endpoint selecting BC1's 4 color and 3 color mode respectively
+        let block = |color1: u16| [0xF800_u16.to_le_bytes(), color1.to_le_bytes(), [0; 2], [0; 2]].concat();
+        let (current, prev) = (block(0x001F), block(0xFFFF));
+        let image = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255]));
+        let prev_info = (prev.as_slice(), 4, 4, 0, 0);
+
+        for (skip_mode, expected) in [(SkipMode::Bitwise, &current), (SkipMode::Pixels, &prev)] {
+            let mut data = current.clone();
+            let options = EncodeOptions { skip_mode, ..Default::default() };
+            reuse_prev_blocks(&mut data, &image, BcFormat::Bc1, prev_info, &options).unwrap();
+            assert_eq!(&data, expected);
+        }
+    }
+
+    /// Whether a block of BC4 source pixels with red 110 reuses the previous frame's block, which decodes to red 100
+    fn reuses_bc4_block(options: EncodeOptions) -> bool {
+        let block = |red: u8| [red, red, 0, 0, 0, 0, 0, 0];
+        let prev = block(100);
+        let mut data = block(110).to_vec();
+        let image = RgbaImage::from_pixel(4, 4, Rgba([110, 0, 0, 255]));
+        reuse_prev_blocks(&mut data, &image, BcFormat::Bc4, (prev.as_slice(), 4, 4, 0, 0), &options).unwrap();
+        data == prev
+    }
+
+    #[test]
+    fn reuses_blocks_within_max_delta() {
+        assert!(reuses_bc4_block(EncodeOptions { max_delta: Some(10), ..Default::default() }));
+        assert!(!reuses_bc4_block(EncodeOptions { max_delta: Some(9), ..Default::default() }));
+    }
+
+    #[test]
+    fn reuses_blocks_within_max_mse() {
+        assert!(reuses_bc4_block(EncodeOptions { max_mse: Some(100.0), ..Default::default() }));
+        assert!(!reuses_bc4_block(EncodeOptions { max_mse: Some(99.9), ..Default::default() }));
+        // both thresholds must hold
+        assert!(!reuses_bc4_block(EncodeOptions { max_delta: Some(10), max_mse: Some(99.9), ..Default::default() }));
+    }
+
+    #[test]
+    fn bleeding_changes_only_bc7_blocks() {
+        // two opaque red pixels in a block whose other pixels are transparent blue
+        let mut image = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 255, 0]));
+        image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        image.put_pixel(1, 0, Rgba([255, 0, 0, 255]));
+        let bled = EncodeOptions { bleed_colors: true, ..Default::default() };
+
+        for (format, changes) in [(BcFormat::Bc1, false), (BcFormat::Bc4, false), (BcFormat::Bc7, true)] {
+            let (blocks, _) = encode(&image, format, BcQuality::Fast, false, None, &Default::default()).unwrap();
+            let (bled_blocks, _) = encode(&image, format, BcQuality::Fast, false, None, &bled).unwrap();
+            assert_eq!(blocks != bled_blocks, changes, "{format:?}");
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt;
+use std::path::PathBuf;
+use image_dds::error::SurfaceError;
+
+#[derive(Debug)]
+pub enum Error {
+    /// An image could not be loaded or written
+    Image(image::ImageError),
+    /// Block compression or decompression failed
+    Surface(SurfaceError),
+    /// A file could not be read or written
+    Io(PathBuf, std::io::Error),
+    /// A file or buffer is not valid for its format
+    Parse(String),
+    /// The arguments are inconsistent with each other or with the data
+    InvalidInput(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Image(e) => write!(f, "Failed to load image: {e}"),
+            Error::Surface(e) => write!(f, "{e}"),
+            Error::Io(path, e) => write!(f, "Failed to access '{}' due to '{e}'", path.display()),
+            Error::Parse(msg) | Error::InvalidInput(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Image(e) => Some(e),
+            Error::Surface(e) => Some(e),
+            Error::Io(_, e) => Some(e),
+            Error::Parse(_) | Error::InvalidInput(_) => None,
+        }
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Error::Image(e)
+    }
+}
+
+impl From<SurfaceError> for Error {
+    fn from(e: SurfaceError) -> Self {
+        Error::Surface(e)
+    }
+}
+
+#[cfg(feature = "python")]
+impl From<Error> for pyo3::PyErr {
+    fn from(e: Error) -> Self {
+        pyo3::exceptions::PyValueError::new_err(e.to_string())
+    }
+}
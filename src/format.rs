@@ -1,34 +1,105 @@
+use image::Rgba;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
-#[pyclass]
-#[derive(Clone, Debug)]
+/// The alpha below which pixels are transparent in BC1's 1-bit alpha, unless configured otherwise
+pub const DEFAULT_ALPHA_CUTOFF: u8 = 128;
+
+/// The compressed blocks, width, height, delta offset_x1 and delta offset_y1 (prev - this) of the previous frame
+pub type PrevInfo<'a> = (&'a [u8], u32, u32, i32, i32);
+
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BcFormat {
     Bc1,
     Bc4,
     Bc7,
 }
 
-#[pyclass]
-#[derive(Clone, Debug)]
+impl BcFormat {
+    pub fn block_size(&self) -> usize {
+        match self {
+            BcFormat::Bc1 | BcFormat::Bc4 => 8,
+            BcFormat::Bc7 => 16,
+        }
+    }
+
+    /// The block that decodes to a fully transparent (or black for BC4) 4x4 area, used for skipped blocks
+    pub fn transparent_block(&self) -> Vec<u8> {
+        match self {
+            BcFormat::Bc1 => vec![0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF],
+            BcFormat::Bc4 => vec![0_u8; 8],
+            BcFormat::Bc7 => {
+                let mut block = vec![0_u8; 16];
+                block[0] = 0x40;
+                block
+            },
+        }
+    }
+
+    /// Whether a source pixel is invisible once compressed: BC1 only has 1-bit alpha, which is cleared below
+    /// `alpha_cutoff`, BC4 only stores the red channel and BC7 pixels without alpha are not drawn
+    pub fn is_transparent(&self, pixel: &Rgba<u8>, alpha_cutoff: u8) -> bool {
+        match self {
+            BcFormat::Bc1 => pixel[3] < alpha_cutoff,
+            BcFormat::Bc4 => pixel[0] == 0,
+            BcFormat::Bc7 => pixel[3] == 0,
+        }
+    }
+
+    /// Whether preprocessing crops a source pixel away. Cropped pixels are always transparent, but for BC4 and BC7 the
+    /// crop rule is stricter than `is_transparent`, as it has always been: BC4 pixels also need no green and blue and
+    /// BC7 pixels no color. Blocks of pixels that are kept but transparent still compress to the transparent block
+    pub fn is_empty(&self, pixel: &Rgba<u8>, alpha_cutoff: u8) -> bool {
+        self.is_transparent(pixel, alpha_cutoff) && match self {
+            BcFormat::Bc1 => true,
+            BcFormat::Bc4 => pixel[1] == 0 && pixel[2] == 0,
+            BcFormat::Bc7 => pixel[0] == 0 && pixel[1] == 0 && pixel[2] == 0,
+        }
+    }
+}
+
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BcQuality {
     Fast,
     Normal,
     Slow
 }
 
-#[pyclass]
+/// How a block is compared with the block at the same position in the previous frame to decide whether it is skipped
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SkipMode {
+    /// The compressed blocks must be bit-for-bit identical
+    #[default]
+    Bitwise,
+    /// The compressed blocks must decode to identical pixels, e.g. BC1 blocks with swapped endpoints
+    Pixels,
+}
+
+/// How semi-transparent pixels are turned into BC1's 1-bit alpha
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AlphaDither {
+    /// Pixels below the alpha cutoff are transparent
+    #[default]
+    Threshold,
+    /// A 4x4 Bayer pattern around the alpha cutoff
+    Ordered,
+    /// Floyd-Steinberg error diffusion around the alpha cutoff
+    ErrorDiffusion,
+}
+
+#[cfg_attr(feature = "python", pyclass(get_all))]
 #[derive(Clone, Debug)]
 pub struct DrawCall {
-    #[pyo3(get)]
     pub skip: u8,
-    #[pyo3(get)]
     pub draw: u8,
 }
 
-#[pymethods]
 impl DrawCall {
-    #[new]
     pub fn new(skip: u8, draw: u8) -> Self {
         Self { skip, draw }
     }
-}
\ No newline at end of file
+}
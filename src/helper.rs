@@ -1,6 +1,35 @@
-use crate::format::DrawCall;
+use std::ops::Deref;
+use image::{ImageBuffer, Rgba};
+use crate::format::{BcFormat, DrawCall};
 use crate::pack::BlockWord;
 
+/// Replaces every block whose source pixels are all transparent (see `BcFormat::is_transparent`) with the format's
+/// transparent block, so that it is skipped regardless of the bit pattern the encoder chose for it
+pub fn canonicalize_transparent_blocks<C: Deref<Target = [u8]>>(
+    data: &mut [u8],
+    image: &ImageBuffer<Rgba<u8>, C>,
+    format: BcFormat,
+    alpha_cutoff: u8,
+) {
+    let (width, height) = image.dimensions();
+    let block_width = width.div_ceil(4);
+    let block_size = format.block_size();
+    let transparent_block = format.transparent_block();
+
+    for (block_idx, block) in data.chunks_exact_mut(block_size).enumerate() {
+        let block_x = block_idx as u32 % block_width * 4;
+        let block_y = block_idx as u32 / block_width * 4;
+
+        let is_transparent = (block_y..(block_y + 4).min(height)).all(|y| {
+            (block_x..(block_x + 4).min(width)).all(|x| format.is_transparent(image.get_pixel(x, y), alpha_cutoff))
+        });
+
+        if is_transparent {
+            block.copy_from_slice(&transparent_block);
+        }
+    }
+}
+
 #[inline]
 pub fn block_is_skip<T: Copy + Eq>(
     idx: usize,
@@ -34,7 +63,7 @@ pub fn encode_blocks<W: BlockWord>(
 ) -> (Vec<DrawCall>, Vec<u8>) {
     let blocks = W::pack(blocks);
     let prev_info = prev_info.map(|(blocks, pw, ph, ox, oy)| {
-        (W::pack(blocks), (pw + 3)/4, (ph + 3)/4, ox/4, oy/4)
+        (W::pack(blocks), pw.div_ceil(4), ph.div_ceil(4), ox/4, oy/4)
     });
     let transparent_block = W::from_bytes(transparent_block);
 
@@ -66,4 +95,47 @@ pub fn encode_blocks<W: BlockWord>(
     }
 
     (commands, W::unpack(&drawn_blocks))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use image::RgbaImage;
+    use super::*;
+    use crate::encode_decode::generate_commands;
+
+    /// Canonicalizes a transparent block followed by an opaque one, both with garbage bits, and generates commands
+    fn assert_transparent_block_is_skipped(format: BcFormat, transparent: Rgba<u8>) {
+        let mut image = RgbaImage::from_pixel(8, 4, transparent);
+        for y in 0..4 {
+            for x in 4..8 {
+                image.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+        let block_size = format.block_size();
+        let mut data = vec![0x5A; block_size * 2];
+
+        canonicalize_transparent_blocks(&mut data, &image, format, 128);
+        assert_eq!(data[..block_size], format.transparent_block());
+        assert!(data[block_size..].iter().all(|&byte| byte == 0x5A));
+
+        let (commands, drawn) = generate_commands(&data, 8, 4, format, None);
+        assert_eq!(commands.iter().map(|cmd| (cmd.skip, cmd.draw)).collect::<Vec<_>>(), [(1, 1)]);
+        assert_eq!(drawn, data[block_size..]);
+    }
+
+    #[test]
+    fn transparent_bc1_blocks_become_skips() {
+        assert_transparent_block_is_skipped(BcFormat::Bc1, Rgba([255, 0, 0, 0]));
+    }
+
+    #[test]
+    fn black_bc4_blocks_become_skips() {
+        // only the red channel is stored
+        assert_transparent_block_is_skipped(BcFormat::Bc4, Rgba([0, 255, 255, 255]));
+    }
+
+    #[test]
+    fn transparent_bc7_blocks_become_skips() {
+        assert_transparent_block_is_skipped(BcFormat::Bc7, Rgba([10, 20, 30, 0]));
+    }
+}
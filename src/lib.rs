@@ -1,30 +1,39 @@
-mod format;
-mod encode_decode;
+pub mod error;
+pub mod format;
+pub mod encode_decode;
 mod helper;
 mod pack;
 
 mod bc1_transparency;
-mod preprocess;
-mod render;
+pub mod preprocess;
+pub mod render;
+pub mod palette;
+pub mod sld;
+pub mod slp;
+pub mod smx;
+pub mod animation;
+pub mod batch;
+pub mod metrics;
+pub mod convert;
+pub mod dds;
 
-use pyo3::prelude::*;
-use crate::format::{BcFormat, BcQuality, DrawCall};
-use crate::encode_decode::{encode, decode};
-use crate::preprocess::{preprocess_frames, ProcessedFrame};
-use crate::render::{render_frames};
+#[cfg(feature = "python")]
+mod python;
 
-#[pymodule]
-#[pyo3(name = "aoe2_tex_com")]
-fn aoe2_tex_com(pid: &Bound<PyModule>) -> PyResult<()> {
-    pid.add_class::<BcFormat>()?;
-    pid.add_class::<BcQuality>()?;
-    pid.add_class::<ProcessedFrame>()?;
-    pid.add_class::<DrawCall>()?;
-
-    pid.add_function(wrap_pyfunction!(encode, pid)?)?;
-    pid.add_function(wrap_pyfunction!(decode, pid)?)?;
-    pid.add_function(wrap_pyfunction!(preprocess_frames, pid)?)?;
-    pid.add_function(wrap_pyfunction!(render_frames, pid)?)?;
-    
-    Ok(())
-}
\ No newline at end of file
+pub use crate::error::{Error, Result};
+pub use crate::format::{AlphaDither, BcFormat, BcQuality, DrawCall, PrevInfo, SkipMode};
+pub use crate::encode_decode::{encode, decode, generate_commands, EncodeOptions};
+pub use crate::preprocess::{
+    bleed_colors, preprocess_frames, preprocess_frames_with, preprocess_images, preprocess_images_with,
+    preprocess_layer_frames, preprocess_layer_images, FrameLayers, LayerKind, ProcessedFrame,
+};
+pub use crate::palette::Palette;
+pub use crate::render::{render_frames, PlayerColor, RenderOptions};
+pub use crate::animation::{decode_animation, encode_animation, LayerDecoder, LayerEncoder};
+pub use crate::batch::{decode_animations, decode_batch, encode_animations, encode_batch};
+pub use crate::convert::{encode_sld, slp_to_sld, smx_to_sld};
+pub use crate::dds::{decode_dds, encode_dds, to_dds};
+pub use crate::metrics::{compare_images, measure_quality, QualityMetrics};
+pub use crate::sld::{SldFile, SldFrame, SldHeader, SldLayer, SldLayerHeader};
+pub use crate::slp::{DecodedSlpFrame, SlpFile, SlpFrame, SlpHeader, SlpPixel};
+pub use crate::smx::{DecodedSmxFrame, SmxFile, SmxFrame, SmxHeader, SmxLayer, SmxPixel};
@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use image::{GrayImage, RgbaImage};
+
+use aoe2_tex_com::format::DEFAULT_ALPHA_CUTOFF;
+use aoe2_tex_com::sld::{LAYER_DAMAGE_MASK, LAYER_MAIN, LAYER_PLAYER_COLOR, LAYER_SHADOW, LAYER_UNKNOWN, SLD_VERSION};
+use aoe2_tex_com::{
+    encode_animation, measure_quality, preprocess_frames, render_frames, AlphaDither, BcFormat, BcQuality,
+    EncodeOptions, Error, LayerDecoder, Palette, PlayerColor, RenderOptions, Result, SkipMode, SldFile, SldFrame,
+    SldLayer,
+};
+
+#[derive(Parser)]
+#[command(name = "aoe2-tex-com", version, about = "Encode, decode, render and inspect AoE2 DE SLD files")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Decode every layer of every frame of an SLD file to PNGs
+    Extract {
+        sld: PathBuf,
+        out_dir: PathBuf,
+    },
+    /// Build an SLD file with a main layer from PNG frames that share an anchor point
+    Build {
+        out: PathBuf,
+        /// The anchor (hotspot) of the frames as x,y
+        #[arg(long, value_parser = parse_pair::<i32>)]
+        anchor: (i32, i32),
+        #[arg(long, value_enum, default_value_t = Quality::Slow)]
+        quality: Quality,
+        /// Also skip blocks that decode to the same pixels as the previous frame's blocks
+        #[arg(long)]
+        pixel_skips: bool,
+        /// Also skip blocks whose pixels differ by at most this much per channel from the previous frame
+        #[arg(long)]
+        max_delta: Option<u8>,
+        /// Also skip blocks whose pixels have at most this mean squared error from the previous frame
+        #[arg(long)]
+        max_mse: Option<f32>,
+        /// Pixels with a lower alpha are transparent
+        #[arg(long, default_value_t = DEFAULT_ALPHA_CUTOFF)]
+        alpha_cutoff: u8,
+        /// How semi-transparent pixels are turned into 1-bit alpha
+        #[arg(long, value_enum, default_value_t = Dither::Threshold)]
+        alpha_dither: Dither,
+        #[arg(required = true)]
+        frames: Vec<PathBuf>,
+    },
+    /// Render a frame of an SLD file with a player color
+    Render {
+        sld: PathBuf,
+        frame: usize,
+        out: PathBuf,
+        /// The player color as r,g,b
+        #[arg(long, value_parser = parse_color, default_value = "255,0,0")]
+        color: (u8, u8, u8),
+        /// A JASC-PAL palette to take the player color from instead, either a player color palette or, with
+        /// --player, a palette holding the player's color
+        #[arg(long)]
+        palette: Option<PathBuf>,
+        /// The index of the player color in the palette
+        #[arg(long, requires = "palette")]
+        player: Option<usize>,
+        /// The damage percentage of the building
+        #[arg(long)]
+        damage: Option<u32>,
+    },
+    /// Print the header and draw command statistics of an SLD file
+    Inspect {
+        sld: PathBuf,
+    },
+    /// Compress and decompress an image and print how much quality is lost
+    Metrics {
+        image: PathBuf,
+        #[arg(long, value_enum, default_value_t = Quality::Slow)]
+        quality: Quality,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Quality {
+    Fast,
+    Normal,
+    Slow,
+}
+
+impl From<Quality> for BcQuality {
+    fn from(quality: Quality) -> Self {
+        match quality {
+            Quality::Fast => BcQuality::Fast,
+            Quality::Normal => BcQuality::Normal,
+            Quality::Slow => BcQuality::Slow,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Dither {
+    Threshold,
+    Ordered,
+    ErrorDiffusion,
+}
+
+impl From<Dither> for AlphaDither {
+    fn from(dither: Dither) -> Self {
+        match dither {
+            Dither::Threshold => AlphaDither::Threshold,
+            Dither::Ordered => AlphaDither::Ordered,
+            Dither::ErrorDiffusion => AlphaDither::ErrorDiffusion,
+        }
+    }
+}
+
+const LAYERS: [(u8, &str, &str); 5] = [
+    (LAYER_MAIN, "main", "m"),
+    (LAYER_SHADOW, "shadow", "s"),
+    (LAYER_UNKNOWN, "unknown", "u"),
+    (LAYER_DAMAGE_MASK, "damage mask", "d"),
+    (LAYER_PLAYER_COLOR, "player color", "p"),
+];
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(cli.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Extract { sld, out_dir } => extract(&sld, &out_dir),
+        Command::Build {
+            out, anchor, quality, pixel_skips, max_delta, max_mse, alpha_cutoff, alpha_dither, frames
+        } => {
+            let options = EncodeOptions {
+                skip_mode: if pixel_skips { SkipMode::Pixels } else { SkipMode::Bitwise },
+                max_delta,
+                max_mse,
+                alpha_cutoff,
+                alpha_dither: alpha_dither.into(),
+                // the main layer is BC1, whose transparent pixels are always black
+                bleed_colors: false,
+            };
+            build(&out, anchor, quality.into(), &options, frames)
+        }
+        Command::Render { sld, frame, out, color, palette, player, damage } => {
+            let color = match (palette, player) {
+                (Some(palette), Some(player)) => PlayerColor::Indexed(Palette::from_file(palette)?, player),
+                (Some(palette), None) => PlayerColor::Palette(Palette::from_file(palette)?),
+                _ => color.into(),
+            };
+            render(&sld, frame, &out, color, damage)
+        }
+        Command::Inspect { sld } => inspect(&sld),
+        Command::Metrics { image, quality } => metrics(&image, quality.into()),
+    }
+}
+
+fn extract(sld: &Path, out_dir: &Path) -> Result<()> {
+    let file = SldFile::from_file(sld.to_path_buf())?;
+    std::fs::create_dir_all(out_dir).map_err(|e| Error::Io(out_dir.to_path_buf(), e))?;
+
+    let stem = sld.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let mut decoders: HashMap<u8, LayerDecoder> = HashMap::new();
+
+    for (i, frame) in file.frames.iter().enumerate() {
+        for (kind, layer) in frame.layers() {
+            let image = decoders.entry(kind).or_default().decode_layer(layer)?;
+            let suffix = LAYERS.iter().find(|(k, _, _)| *k == kind).map_or("x", |(_, _, suffix)| suffix);
+            save(&out_dir.join(format!("{stem}_{i:03}_{suffix}.png")), &image)?;
+        }
+    }
+
+    println!("Extracted {} frames to '{}'", file.frames.len(), out_dir.display());
+    Ok(())
+}
+
+fn build(
+    out: &Path,
+    anchor: (i32, i32),
+    quality: BcQuality,
+    options: &EncodeOptions,
+    frames: Vec<PathBuf>,
+) -> Result<()> {
+    let frame_info = frames.into_iter().map(|path| (path, anchor)).collect();
+    let (frames, (hotspot_x, hotspot_y), (canvas_width, canvas_height)) =
+        preprocess_frames(frame_info, BcFormat::Bc1, options.alpha_cutoff)?;
+
+    let encoded = encode_animation(&frames, BcFormat::Bc1, quality, options)?;
+
+    let sld_frames = frames.iter()
+        .zip(encoded)
+        .enumerate()
+        .map(|(i, (frame, (commands, blocks, use_prev)))| {
+            let main_layer = SldLayer::from_encoded((commands, blocks), frame, BcFormat::Bc1, use_prev)?;
+            Ok(SldFrame::new(
+                to_u16(canvas_width)?,
+                to_u16(canvas_height)?,
+                to_u16(hotspot_x)?,
+                to_u16(hotspot_y)?,
+                to_u16(i as u32)?,
+                Some(main_layer),
+                None,
+                None,
+                None,
+                None,
+                0,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let num_frames = sld_frames.len();
+    SldFile::new(sld_frames, SLD_VERSION)?.to_file(out.to_path_buf())?;
+
+    println!("Built '{}' with {num_frames} frames", out.display());
+    Ok(())
+}
+
+fn render(sld: &Path, frame_idx: usize, out: &Path, color: PlayerColor, damage: Option<u32>) -> Result<()> {
+    let file = SldFile::from_file(sld.to_path_buf())?;
+    if frame_idx >= file.frames.len() {
+        return Err(Error::InvalidInput(format!(
+            "Frame {frame_idx} does not exist, the file has {} frames", file.frames.len()
+        )));
+    }
+
+    // frames may reuse blocks from the previous frame, so every frame up to the requested one is decoded
+    let mut decoders: HashMap<u8, LayerDecoder> = HashMap::new();
+    let mut decoded: HashMap<u8, RgbaImage> = HashMap::new();
+    for frame in &file.frames[..=frame_idx] {
+        for (kind, layer) in frame.layers() {
+            decoded.insert(kind, decoders.entry(kind).or_default().decode_layer(layer)?);
+        }
+    }
+
+    let frame = &file.frames[frame_idx];
+    let Some(main_layer) = &frame.main_layer else {
+        return Err(Error::InvalidInput(format!("Frame {frame_idx} has no main layer")));
+    };
+    let (width, height) = (main_layer.width(), main_layer.height());
+
+    // mask layers are decoded as RGBA but only their red channel is used
+    let (shadow, shadow_offset) = match &frame.shadow_layer {
+        Some(shadow_layer) => (
+            red_channel(&decoded[&LAYER_SHADOW]),
+            (
+                shadow_layer.header.offset_x1 as i32 - main_layer.header.offset_x1 as i32,
+                shadow_layer.header.offset_y1 as i32 - main_layer.header.offset_y1 as i32,
+            ),
+        ),
+        None => (GrayImage::new(width, height), (0, 0)),
+    };
+    let player_color_mask = match frame.player_color_layer {
+        Some(_) => red_channel(&decoded[&LAYER_PLAYER_COLOR]),
+        None => GrayImage::new(width, height),
+    };
+    let damage_mask = match (damage, frame.damage_mask_layer.as_ref()) {
+        (Some(percent), Some(_)) => Some((&decoded[&LAYER_DAMAGE_MASK], percent)),
+        _ => None,
+    };
+
+    let options = RenderOptions { color, ..RenderOptions::default() };
+    let image = render_frames(
+        &decoded[&LAYER_MAIN],
+        &shadow,
+        shadow_offset,
+        &player_color_mask,
+        damage_mask,
+        &options,
+    )?;
+    save(out, &image)
+}
+
+fn inspect(sld: &Path) -> Result<()> {
+    let file = SldFile::from_file(sld.to_path_buf())?;
+    let header = &file.header;
+    println!(
+        "version {}, {} frames, unknowns (0x{:04X}, 0x{:04X}, 0x{:08X})",
+        header.version, header.num_frames, header.unknown1, header.unknown2, header.unknown3
+    );
+
+    let (mut total_drawn, mut total_skipped) = (0_usize, 0_usize);
+    for frame in &file.frames {
+        println!(
+            "frame {}: canvas {}x{}, hotspot ({}, {}), type 0x{:02X}",
+            frame.frame_index, frame.canvas_width, frame.canvas_height,
+            frame.canvas_hotspot_x, frame.canvas_hotspot_y, frame.frame_type
+        );
+        for (kind, layer) in frame.layers() {
+            let name = LAYERS.iter().find(|(k, _, _)| *k == kind).map_or("unknown", |(_, name, _)| name);
+            let drawn: usize = layer.commands.iter().map(|cmd| cmd.draw as usize).sum();
+            let skipped: usize = layer.commands.iter().map(|cmd| cmd.skip as usize).sum();
+            total_drawn += drawn;
+            total_skipped += skipped;
+
+            let header = &layer.header;
+            println!(
+                "  {name:<12} ({}, {}, {}, {}) {:?}{}: {} commands, {drawn} drawn, {skipped} skipped blocks",
+                header.offset_x1, header.offset_y1, header.offset_x2, header.offset_y2,
+                layer.format(), if layer.uses_prev() { ", uses previous frame" } else { "" },
+                layer.commands.len()
+            );
+        }
+    }
+
+    println!("total: {total_drawn} drawn, {total_skipped} skipped blocks");
+    Ok(())
+}
+
+fn metrics(path: &Path, quality: BcQuality) -> Result<()> {
+    let image = image::open(path).map_err(Error::Image)?.to_rgba8();
+    let metrics = measure_quality(&image, BcFormat::Bc1, quality, &EncodeOptions::default())?;
+
+    println!("PSNR {:.2} dB, SSIM {:.4}, max error {}", metrics.psnr, metrics.ssim, metrics.max_error);
+    println!(
+        "opaque: PSNR {:.2} dB, SSIM {:.4}, max error {}",
+        metrics.opaque_psnr, metrics.opaque_ssim, metrics.opaque_max_error
+    );
+    if let Some(alpha_changed) = metrics.alpha_changed {
+        println!("{alpha_changed} pixels changed alpha");
+    }
+    Ok(())
+}
+
+fn red_channel(image: &RgbaImage) -> GrayImage {
+    GrayImage::from_fn(image.width(), image.height(), |x, y| image::Luma([image.get_pixel(x, y)[0]]))
+}
+
+fn save(path: &Path, image: &RgbaImage) -> Result<()> {
+    image.save(path).map_err(Error::Image)
+}
+
+fn to_u16(value: u32) -> Result<u16> {
+    u16::try_from(value).map_err(|_| Error::InvalidInput(format!("{value} does not fit in an SLD header")))
+}
+
+fn parse_pair<T: std::str::FromStr>(s: &str) -> std::result::Result<(T, T), String> {
+    let (a, b) = s.split_once(',').ok_or_else(|| format!("expected two comma separated values, got '{s}'"))?;
+    let parse = |v: &str| v.trim().parse().map_err(|_| format!("invalid value '{v}'"));
+    Ok((parse(a)?, parse(b)?))
+}
+
+fn parse_color(s: &str) -> std::result::Result<(u8, u8, u8), String> {
+    let parts = s.split(',')
+        .map(|v| v.trim().parse().map_err(|_| format!("invalid color component '{v}'")))
+        .collect::<std::result::Result<Vec<u8>, _>>()?;
+    match parts[..] {
+        [r, g, b] => Ok((r, g, b)),
+        _ => Err(format!("expected r,g,b, got '{s}'")),
+    }
+}
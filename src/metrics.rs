@@ -0,0 +1,199 @@
+use std::ops::Deref;
+use image::{ImageBuffer, Rgba, RgbaImage};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use crate::encode_decode::{decode, encode, EncodeOptions};
+use crate::error::{Error, Result};
+use crate::format::{BcFormat, BcQuality};
+
+/// The side of the windows SSIM is computed over, windows overlap by half
+const SSIM_WINDOW: u32 = 8;
+const SSIM_C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const SSIM_C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+/// How much a decoded image differs from the original. The overall metrics compare premultiplied colors, so the color
+/// of invisible pixels does not count, and the opaque metrics only consider the color of pixels that are not
+/// transparent in the original, see `BcFormat::is_transparent`. A PSNR of infinity means the images are identical
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QualityMetrics {
+    pub psnr: f64,
+    pub ssim: f64,
+    pub max_error: u8,
+    pub opaque_psnr: f64,
+    pub opaque_ssim: f64,
+    pub opaque_max_error: u8,
+    /// The number of pixels whose alpha differs between the images, `None` for BC4, which has no alpha
+    pub alpha_changed: Option<u32>,
+}
+
+/// Compresses and decompresses the image and compares the result with the image
+pub fn measure_quality<C: Deref<Target = [u8]>>(
+    image: &ImageBuffer<Rgba<u8>, C>,
+    format: BcFormat,
+    quality: BcQuality,
+    options: &EncodeOptions,
+) -> Result<QualityMetrics> {
+    let (blocks, _) = encode(image, format, quality, false, None, options)?;
+    let (decoded, _) = decode(blocks, image.width(), image.height(), format, None, None)?;
+    compare_images(image, &decoded, format, options.alpha_cutoff)
+}
+
+/// Compares a decoded image with its original. Only the channels the format stores are compared, i.e. red for BC4
+pub fn compare_images<C: Deref<Target = [u8]>>(
+    original: &ImageBuffer<Rgba<u8>, C>,
+    decoded: &RgbaImage,
+    format: BcFormat,
+    alpha_cutoff: u8,
+) -> Result<QualityMetrics> {
+    if original.dimensions() != decoded.dimensions() {
+        return Err(Error::InvalidInput(format!(
+            "Decoded image dimensions {:?} do not match the original dimensions {:?}",
+            decoded.dimensions(), original.dimensions()
+        )));
+    }
+
+    let (channels, opaque_channels): (&[usize], &[usize]) = match format {
+        BcFormat::Bc4 => (&[0], &[0]),
+        BcFormat::Bc1 | BcFormat::Bc7 => (&[0, 1, 2, 3], &[0, 1, 2]),
+    };
+
+    let all = vec![true; (original.width() * original.height()) as usize];
+    let opaque: Vec<bool> = original.pixels().map(|pixel| !format.is_transparent(pixel, alpha_cutoff)).collect();
+
+    let (premultiplied, decoded_premultiplied) = match format {
+        BcFormat::Bc4 => (to_owned(original), decoded.clone()),
+        BcFormat::Bc1 | BcFormat::Bc7 => (premultiply(original), premultiply(decoded)),
+    };
+
+    let (psnr, max_error) = error_stats(&premultiplied, &decoded_premultiplied, channels, &all);
+    let (opaque_psnr, opaque_max_error) = error_stats(original, decoded, opaque_channels, &opaque);
+
+    let alpha_changed = (format != BcFormat::Bc4).then(|| {
+        original.pixels()
+            .zip(decoded.pixels())
+            .filter(|(a, b)| a[3] != b[3])
+            .count() as u32
+    });
+
+    Ok(QualityMetrics {
+        psnr,
+        ssim: ssim(&premultiplied, &decoded_premultiplied, channels, &all),
+        max_error,
+        opaque_psnr,
+        opaque_ssim: ssim(original, decoded, opaque_channels, &opaque),
+        opaque_max_error,
+        alpha_changed,
+    })
+}
+
+fn to_owned<C: Deref<Target = [u8]>>(image: &ImageBuffer<Rgba<u8>, C>) -> RgbaImage {
+    RgbaImage::from_raw(image.width(), image.height(), image.as_raw().to_vec()).unwrap()
+}
+
+fn premultiply<C: Deref<Target = [u8]>>(image: &ImageBuffer<Rgba<u8>, C>) -> RgbaImage {
+    RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        let scale = |v: u8| ((v as u32 * a as u32 + 127) / 255) as u8;
+        Rgba([scale(r), scale(g), scale(b), a])
+    })
+}
+
+/// The PSNR and the maximum error over the given channels of the pixels in the mask
+fn error_stats<C: Deref<Target = [u8]>>(
+    original: &ImageBuffer<Rgba<u8>, C>,
+    decoded: &RgbaImage,
+    channels: &[usize],
+    mask: &[bool],
+) -> (f64, u8) {
+    let (mut squared_error, mut count, mut max_error) = (0_u64, 0_u64, 0_u8);
+
+    for ((a, b), _) in original.pixels().zip(decoded.pixels()).zip(mask).filter(|(_, in_mask)| **in_mask) {
+        for &channel in channels {
+            let delta = a[channel].abs_diff(b[channel]);
+            squared_error += delta as u64 * delta as u64;
+            max_error = max_error.max(delta);
+            count += 1;
+        }
+    }
+
+    if squared_error == 0 {
+        return (f64::INFINITY, 0);
+    }
+    let mse = squared_error as f64 / count as f64;
+    (10.0 * (255.0 * 255.0 / mse).log10(), max_error)
+}
+
+/// The mean SSIM over the given channels of overlapping windows, only counting the pixels in the mask
+fn ssim<C: Deref<Target = [u8]>>(
+    original: &ImageBuffer<Rgba<u8>, C>,
+    decoded: &RgbaImage,
+    channels: &[usize],
+    mask: &[bool],
+) -> f64 {
+    let (width, height) = original.dimensions();
+    let (window_width, window_height) = (SSIM_WINDOW.min(width), SSIM_WINDOW.min(height));
+    let starts = |size: u32, window: u32| {
+        let step = (window / 2).max(1);
+        (0..=size - window).step_by(step as usize).collect::<Vec<_>>()
+    };
+
+    let (mut total, mut windows) = (0.0, 0_u32);
+    for &y0 in &starts(height, window_height) {
+        for &x0 in &starts(width, window_width) {
+            for &channel in channels {
+                let samples: Vec<(f64, f64)> = (y0..y0 + window_height)
+                    .flat_map(|y| (x0..x0 + window_width).map(move |x| (x, y)))
+                    .filter(|&(x, y)| mask[(y * width + x) as usize])
+                    .map(|(x, y)| (original.get_pixel(x, y)[channel] as f64, decoded.get_pixel(x, y)[channel] as f64))
+                    .collect();
+                if samples.is_empty() {
+                    continue;
+                }
+
+                let n = samples.len() as f64;
+                let mean_a = samples.iter().map(|s| s.0).sum::<f64>() / n;
+                let mean_b = samples.iter().map(|s| s.1).sum::<f64>() / n;
+                let (mut var_a, mut var_b, mut covariance) = (0.0, 0.0, 0.0);
+                for (a, b) in &samples {
+                    var_a += (a - mean_a) * (a - mean_a) / n;
+                    var_b += (b - mean_b) * (b - mean_b) / n;
+                    covariance += (a - mean_a) * (b - mean_b) / n;
+                }
+
+                total += ((2.0 * mean_a * mean_b + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+                    / ((mean_a * mean_a + mean_b * mean_b + SSIM_C1) * (var_a + var_b + SSIM_C2));
+                windows += 1;
+            }
+        }
+    }
+
+    if windows == 0 {
+        return 1.0;
+    }
+    total / windows as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_alpha_changes_only_for_formats_with_alpha() {
+        let original = RgbaImage::from_fn(8, 8, |x, y| {
+            Rgba([x as u8 * 30, y as u8 * 30, 90, if x < 4 { 0 } else { 200 }])
+        });
+        let mut decoded = original.clone();
+        decoded.put_pixel(5, 5, Rgba([150, 150, 90, 255]));
+
+        let bc1 = compare_images(&original, &decoded, BcFormat::Bc1, 128).unwrap();
+        assert_eq!(bc1.alpha_changed, Some(1));
+
+        let bc4 = compare_images(&original, &decoded, BcFormat::Bc4, 128).unwrap();
+        assert_eq!(bc4.alpha_changed, None);
+        assert_eq!(bc4.max_error, 0);
+
+        let identical = compare_images(&original, &original, BcFormat::Bc7, 128).unwrap();
+        assert_eq!((identical.psnr, identical.alpha_changed), (f64::INFINITY, Some(0)));
+    }
+}
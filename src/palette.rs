@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use crate::error::{Error, Result};
+
+const JASC_SIGNATURE: &str = "JASC-PAL";
+
+/// The colors of a palette, e.g. the game's palettes used by SLP and SMX sprites or a player color palette
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    pub fn new(colors: Vec<[u8; 3]>) -> Self {
+        Self { colors }
+    }
+
+    /// Parses a JASC-PAL palette, the format of the game's palettes including the player color palettes. Their colors
+    /// may have a fourth alpha value, which is ignored, and lines starting with `#` are comments
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let text = std::str::from_utf8(bytes)
+            .map_err(|_| Error::Parse("A JASC-PAL palette must be text".to_string()))?;
+        let mut lines = text.trim_start_matches('\u{feff}').lines()
+            .map(str::trim)
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        let mut next = |name: &str| lines.next()
+            .ok_or_else(|| Error::Parse(format!("The palette ends before its {name}")));
+
+        let (_, signature) = next("signature")?;
+        if signature != JASC_SIGNATURE {
+            return Err(Error::Parse(format!("Invalid palette signature '{signature}', expected '{JASC_SIGNATURE}'")));
+        }
+        next("version")?;
+        let (line_idx, count) = next("color count")?;
+        let count: usize = count.parse()
+            .map_err(|_| Error::Parse(format!("Invalid color count '{count}' on line {}", line_idx + 1)))?;
+
+        // the count is not trusted to size the colors, a palette that has fewer fails below
+        let mut colors = Vec::new();
+        for _ in 0..count {
+            let (line_idx, line) = next("colors")?;
+            let values = line.split_whitespace()
+                .map(|value| value.parse::<u8>())
+                .collect::<std::result::Result<Vec<_>, _>>();
+            match values.as_deref() {
+                Ok([r, g, b] | [r, g, b, _]) => colors.push([*r, *g, *b]),
+                _ => return Err(Error::Parse(format!("Invalid color '{line}' on line {}", line_idx + 1))),
+            }
+        }
+
+        Ok(Self { colors })
+    }
+
+    pub fn from_file(path: PathBuf) -> Result<Self> {
+        let bytes = std::fs::read(&path).map_err(|e| Error::Io(path.clone(), e))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Writes the palette as JASC-PAL
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = format!("{JASC_SIGNATURE}\r\n0100\r\n{}\r\n", self.colors.len());
+        for [r, g, b] in &self.colors {
+            out.push_str(&format!("{r} {g} {b}\r\n"));
+        }
+        out.into_bytes()
+    }
+
+    pub fn to_file(&self, path: PathBuf) -> Result<()> {
+        std::fs::write(&path, self.to_bytes()).map_err(|e| Error::Io(path.clone(), e))
+    }
+
+    pub fn color(&self, index: usize) -> Result<(u8, u8, u8)> {
+        self.colors.get(index)
+            .map(|&[r, g, b]| (r, g, b))
+            .ok_or_else(|| Error::InvalidInput(format!(
+                "Palette index {index} is out of range for a palette with {} colors", self.colors.len()
+            )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_bytes() {
+        let palette = Palette::new(vec![[0, 0, 0], [255, 128, 1], [12, 34, 56]]);
+        assert_eq!(Palette::from_bytes(&palette.to_bytes()).unwrap(), palette);
+    }
+
+    #[test]
+    fn reads_comments_and_alpha() {
+        let bytes = "\u{feff}JASC-PAL\n0100\n# player 1\n2\n255 0 0 255\n\n0 0 255 0\n";
+        assert_eq!(Palette::from_bytes(bytes.as_bytes()).unwrap().colors, [[255, 0, 0], [0, 0, 255]]);
+    }
+
+    #[test]
+    fn rejects_missing_colors() {
+        for count in ["2", "18446744073709551615"] {
+            let bytes = format!("JASC-PAL\r\n0100\r\n{count}\r\n255 0 0\r\n");
+            assert!(matches!(Palette::from_bytes(bytes.as_bytes()), Err(Error::Parse(_))));
+        }
+    }
+}
@@ -1,72 +1,295 @@
 use crate::BcFormat;
-use std::path::PathBuf;
-use image::{ImageBuffer, Rgba};
-use pyo3::exceptions::PyValueError;
+use std::path::{Path, PathBuf};
+use image::{ImageBuffer, Rgba, RgbaImage};
+use rayon::prelude::*;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
+use crate::error::{Error, Result};
 
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 #[derive(Debug, Clone)]
 pub struct ProcessedFrame {
-    #[pyo3(get)]
-    image_bytes: Vec<u8>,
-    #[pyo3(get)]
-    offset_x1: u32,
-    #[pyo3(get)]
-    offset_y1: u32,
-    #[pyo3(get)]
-    offset_x2: u32,
-    #[pyo3(get)]
-    offset_y2: u32,
-    #[pyo3(get)]
-    anchor_x: i32,
-    #[pyo3(get)]
-    anchor_y: i32,
+    pub image: RgbaImage,
+    pub offset_x1: u32,
+    pub offset_y1: u32,
+    pub offset_x2: u32,
+    pub offset_y2: u32,
+    pub anchor_x: i32,
+    pub anchor_y: i32,
 }
 
+/// The meaning of a layer of an SLD frame, which decides what an empty pixel is independently of the compression format
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LayerKind {
+    Main,
+    Shadow,
+    PlayerColor,
+    DamageMask,
+}
+
+impl LayerKind {
+    /// Whether a pixel has no content, in terms of the format predicates. Main layer pixels are empty like BC1 pixels,
+    /// below the alpha cutoff. Shadow and player color pixels store their intensity in the red channel, so they are
+    /// empty where BC4 stores nothing. Damage mask pixels use the red, green and blue channels, so they are only empty
+    /// if all three are zero, which is BC4's crop rule
+    pub fn is_empty(&self, pixel: &Rgba<u8>, alpha_cutoff: u8) -> bool {
+        match self {
+            LayerKind::Main => BcFormat::Bc1.is_empty(pixel, alpha_cutoff),
+            LayerKind::Shadow | LayerKind::PlayerColor => BcFormat::Bc4.is_transparent(pixel, alpha_cutoff),
+            LayerKind::DamageMask => BcFormat::Bc4.is_empty(pixel, alpha_cutoff),
+        }
+    }
+}
+
+/// Loads the images at the given paths in parallel and preprocesses them, see `preprocess_images`
 #[allow(clippy::type_complexity)]
-#[pyfunction]
-#[pyo3(name = "preprocess", signature = (frame_info, format = BcFormat::Bc1))]
 pub fn preprocess_frames(
     frame_info: Vec<(PathBuf, (i32, i32))>,
     format: BcFormat,
-) -> PyResult<(Vec<ProcessedFrame>, (u32, u32), (u32, u32))> {
-    if frame_info.is_empty() {
-        return Err(PyValueError::new_err("No frames provided"));
+    alpha_cutoff: u8,
+) -> Result<(Vec<ProcessedFrame>, (u32, u32), (u32, u32))> {
+    preprocess_frames_with(frame_info, |pixel| format.is_empty(pixel, alpha_cutoff))
+}
+
+/// Loads the images at the given paths in parallel and preprocesses them, see `preprocess_images_with`
+#[allow(clippy::type_complexity)]
+pub fn preprocess_frames_with(
+    frame_info: Vec<(PathBuf, (i32, i32))>,
+    is_empty: impl Fn(&Rgba<u8>) -> bool,
+) -> Result<(Vec<ProcessedFrame>, (u32, u32), (u32, u32))> {
+    let frames = frame_info.into_par_iter()
+        .map(|(path, anchor)| Ok((load_image(&path)?, anchor)))
+        .collect::<Result<Vec<_>>>()?;
+
+    preprocess_images_with(frames, is_empty)
+}
+
+/// Preprocesses the frames with pixels being empty as decided by `BcFormat::is_empty` with the alpha cutoff, which
+/// should match the one used for encoding, see `preprocess_images_with`
+#[allow(clippy::type_complexity)]
+pub fn preprocess_images(
+    frames: Vec<(RgbaImage, (i32, i32))>,
+    format: BcFormat,
+    alpha_cutoff: u8,
+) -> Result<(Vec<ProcessedFrame>, (u32, u32), (u32, u32))> {
+    preprocess_images_with(frames, |pixel| format.is_empty(pixel, alpha_cutoff))
+}
+
+/// Crops the empty borders of every frame, padding them to multiples of 4 around their anchor, and places them on a
+/// common canvas. `is_empty` decides which pixels are empty, e.g. `LayerKind::is_empty`. Returns the processed frames,
+/// the anchor of the canvas and the size of the canvas
+#[allow(clippy::type_complexity)]
+pub fn preprocess_images_with(
+    frames: Vec<(RgbaImage, (i32, i32))>,
+    is_empty: impl Fn(&Rgba<u8>) -> bool,
+) -> Result<(Vec<ProcessedFrame>, (u32, u32), (u32, u32))> {
+    if frames.is_empty() {
+        return Err(Error::InvalidInput("No frames provided".to_string()));
     }
 
-    let mut processed_frames = Vec::with_capacity(frame_info.len());
+    let bounds: Vec<_> = frames.iter()
+        .map(|(img, anchor)| layer_bounds(img, *anchor, &is_empty))
+        .collect();
 
-    let mut max_dx1 = 0;
-    let mut max_dy1 = 0;
-    let mut max_dx2 = 0;
-    let mut max_dy2 = 0;
-    
-    for (ref path, (anchor_x, anchor_y)) in frame_info.into_iter() {
-        let img = image::open(path)
-            .map_err(|e| PyValueError::new_err(format!(
-                "Failed to load image '{}' due to '{}'", path.display(), e
-            )))?
-            .to_rgba8();
-
-        let (width, height) = img.dimensions();
-        let (x1, y1, x2, y2) = find_bounds(&img, width, height, &format);
-        let (x1, y1, x2, y2) = pad_bounds(x1, y1, x2, y2, anchor_x, anchor_y);
+    let mut canvas = Canvas::default();
+    for (bounds, (_, anchor)) in bounds.iter().zip(&frames) {
+        canvas.extend(*bounds, *anchor);
+    }
+
+    let frame_infos = frames.iter()
+        .zip(bounds)
+        .map(|((img, anchor), bounds)| canvas.place(img, bounds, *anchor))
+        .collect();
+
+    Ok((frame_infos, canvas.hotspot(), canvas.size()))
+}
+
+/// The layer images of an SLD frame, which all share the anchor of the frame
+#[derive(Debug, Clone)]
+pub struct FrameLayers<T> {
+    pub main: T,
+    pub shadow: Option<T>,
+    pub player_color: Option<T>,
+    pub damage_mask: Option<T>,
+}
+
+impl<T> FrameLayers<T> {
+    pub fn new(main: T, shadow: Option<T>, player_color: Option<T>, damage_mask: Option<T>) -> Self {
+        Self { main, shadow, player_color, damage_mask }
+    }
+
+    fn try_map<U>(self, f: impl Fn(T) -> Result<U>) -> Result<FrameLayers<U>> {
+        Ok(FrameLayers {
+            main: f(self.main)?,
+            shadow: self.shadow.map(&f).transpose()?,
+            player_color: self.player_color.map(&f).transpose()?,
+            damage_mask: self.damage_mask.map(&f).transpose()?,
+        })
+    }
+}
+
+/// Loads the layer images at the given paths in parallel and preprocesses them, see `preprocess_layer_images`
+#[allow(clippy::type_complexity)]
+pub fn preprocess_layer_frames(
+    frame_info: Vec<(FrameLayers<PathBuf>, (i32, i32))>,
+    alpha_cutoff: u8,
+) -> Result<(Vec<FrameLayers<ProcessedFrame>>, (u32, u32), (u32, u32))> {
+    let frames = frame_info.into_par_iter()
+        .map(|(paths, anchor)| Ok((paths.try_map(|path| load_image(&path))?, anchor)))
+        .collect::<Result<Vec<_>>>()?;
+
+    preprocess_layer_images(frames, alpha_cutoff)
+}
+
+/// Preprocesses the layers of every frame. The main layer is cropped like `preprocess_images` with the alpha cutoff,
+/// the player color and damage masks are cropped to the same rectangle as the main layer so that they line up with it,
+/// and the shadow is cropped to its own bounds. The canvas covers the main layers and the shadows of all frames.
+/// Returns the processed layers, the anchor of the canvas and the size of the canvas
+#[allow(clippy::type_complexity)]
+pub fn preprocess_layer_images(
+    frames: Vec<(FrameLayers<RgbaImage>, (i32, i32))>,
+    alpha_cutoff: u8,
+) -> Result<(Vec<FrameLayers<ProcessedFrame>>, (u32, u32), (u32, u32))> {
+    if frames.is_empty() {
+        return Err(Error::InvalidInput("No frames provided".to_string()));
+    }
+
+    let bounds: Vec<_> = frames.iter()
+        .map(|(layers, anchor)| {
+            let main = layer_bounds(&layers.main, *anchor, &|pixel| LayerKind::Main.is_empty(pixel, alpha_cutoff));
+            let shadow = layers.shadow.as_ref().map(|shadow| {
+                layer_bounds(shadow, *anchor, &|pixel| LayerKind::Shadow.is_empty(pixel, alpha_cutoff))
+            });
+            (main, shadow)
+        })
+        .collect();
+
+    let mut canvas = Canvas::default();
+    for ((main, shadow), (_, anchor)) in bounds.iter().zip(&frames) {
+        canvas.extend(*main, *anchor);
+        if let Some(shadow) = shadow {
+            canvas.extend(*shadow, *anchor);
+        }
+    }
+
+    let processed = frames.iter()
+        .zip(bounds)
+        .map(|((layers, anchor), (main, shadow))| FrameLayers {
+            main: canvas.place(&layers.main, main, *anchor),
+            shadow: layers.shadow.as_ref().zip(shadow).map(|(img, shadow)| canvas.place(img, shadow, *anchor)),
+            player_color: layers.player_color.as_ref().map(|img| canvas.place(img, main, *anchor)),
+            damage_mask: layers.damage_mask.as_ref().map(|img| canvas.place(img, main, *anchor)),
+        })
+        .collect();
+
+    Ok((processed, canvas.hotspot(), canvas.size()))
+}
+
+/// Fills the color of transparent pixels with the average color of their nearest opaque neighbours, growing outwards
+/// from the opaque pixels, so that compressed edges don't pick up the color of invisible pixels. The alpha is kept.
+/// Only BC7 stores the color of transparent pixels: BC1 decodes them to transparent black and BC4 has no transparency,
+/// so their images are left unchanged
+pub fn bleed_colors(image: &mut RgbaImage, format: BcFormat, alpha_cutoff: u8) {
+    if format != BcFormat::Bc7 {
+        return;
+    }
+
+    let (width, height) = image.dimensions();
+    let index = |x: u32, y: u32| (y * width + x) as usize;
+    let mut filled: Vec<bool> = image.pixels().map(|pixel| !format.is_transparent(pixel, alpha_cutoff)).collect();
+    let mut queued = filled.clone();
+
+    let neighbours = move |x: u32, y: u32| {
+        (-1_i32..=1).flat_map(move |dy| (-1_i32..=1).map(move |dx| (x as i32 + dx, y as i32 + dy)))
+            .filter(move |&(nx, ny)| {
+                (nx, ny) != (x as i32, y as i32) && nx >= 0 && ny >= 0 && nx < width as i32 && ny < height as i32
+            })
+            .map(|(nx, ny)| (nx as u32, ny as u32))
+    };
+
+    let mut frontier = Vec::new();
+    for (x, y, _) in image.enumerate_pixels() {
+        if !filled[index(x, y)] && neighbours(x, y).any(|(nx, ny)| filled[index(nx, ny)]) {
+            frontier.push((x, y));
+            queued[index(x, y)] = true;
+        }
+    }
+
+    while !frontier.is_empty() {
+        let colors: Vec<[u8; 3]> = frontier.iter()
+            .map(|&(x, y)| {
+                let (mut sum, mut count) = ([0_u32; 3], 0_u32);
+                for (nx, ny) in neighbours(x, y).filter(|&(nx, ny)| filled[index(nx, ny)]) {
+                    let pixel = image.get_pixel(nx, ny);
+                    for i in 0..3 {
+                        sum[i] += pixel[i] as u32;
+                    }
+                    count += 1;
+                }
+                sum.map(|v| (v / count) as u8)
+            })
+            .collect();
+
+        for (&(x, y), color) in frontier.iter().zip(colors) {
+            let pixel = image.get_pixel_mut(x, y);
+            pixel.0 = [color[0], color[1], color[2], pixel[3]];
+            filled[index(x, y)] = true;
+        }
+
+        let mut next = Vec::new();
+        for &(x, y) in &frontier {
+            for (nx, ny) in neighbours(x, y) {
+                if !queued[index(nx, ny)] {
+                    queued[index(nx, ny)] = true;
+                    next.push((nx, ny));
+                }
+            }
+        }
+        frontier = next;
+    }
+}
+
+fn load_image(path: &Path) -> Result<RgbaImage> {
+    image::open(path)
+        .map(|img| img.to_rgba8())
+        .map_err(|e| match e {
+            image::ImageError::IoError(e) => Error::Io(path.to_path_buf(), e),
+            e => Error::Image(e),
+        })
+}
+
+/// The furthest any processed frame reaches from its anchor, which gives the hotspot and size of the canvas
+#[derive(Default)]
+struct Canvas {
+    max_dx1: i32,
+    max_dy1: i32,
+    max_dx2: i32,
+    max_dy2: i32,
+}
+
+impl Canvas {
+    fn extend(&mut self, (x1, y1, x2, y2): (i32, i32, i32, i32), (anchor_x, anchor_y): (i32, i32)) {
         let (dx1, dy1, dx2, dy2) = get_detla(x1, y1, x2, y2, anchor_x, anchor_y);
 
-        max_dx1 = max_dx1.max(dx1);
-        max_dy1 = max_dy1.max(dy1);
-        max_dx2 = max_dx2.max(dx2);
-        max_dy2 = max_dy2.max(dy2);
+        self.max_dx1 = self.max_dx1.max(dx1);
+        self.max_dy1 = self.max_dy1.max(dy1);
+        self.max_dx2 = self.max_dx2.max(dx2);
+        self.max_dy2 = self.max_dy2.max(dy2);
+    }
 
-        let anchor_x = anchor_x - x1;
-        let anchor_y = anchor_y - y1;
-        
-        processed_frames.push((img, x1, y1, x2, y2, anchor_x, anchor_y));
+    fn hotspot(&self) -> (u32, u32) {
+        (self.max_dx1 as u32, self.max_dy1 as u32)
     }
 
-    let mut frame_infos = Vec::with_capacity(processed_frames.len());
+    fn size(&self) -> (u32, u32) {
+        ((self.max_dx1 + self.max_dx2) as u32, (self.max_dy1 + self.max_dy2) as u32)
+    }
 
-    for (img, x1, y1, x2, y2, anchor_x, anchor_y) in processed_frames {
+    /// Crops the image to the bounds, filling pixels outside of it with transparency, and offsets it on the canvas
+    fn place(
+        &self, img: &RgbaImage, (x1, y1, x2, y2): (i32, i32, i32, i32), (anchor_x, anchor_y): (i32, i32)
+    ) -> ProcessedFrame {
         let (width ,height) = (img.width() as i32, img.height() as i32);
         let (new_width, new_height) = (x2 - x1, y2 - y1);
         let mut processed_img = ImageBuffer::new(new_width as u32, new_height as u32);
@@ -74,44 +297,46 @@ pub fn preprocess_frames(
             for x in 0..new_width {
                 let src_x = x1 + x;
                 let src_y = y1 + y;
-                
+
                 let pixel = if src_x < 0 || src_y < 0 || src_x >= width || src_y >= height {
                     &Rgba::from([0, 0, 0, 0])
                 } else {
                     img.get_pixel(src_x as u32, src_y as u32)
                 };
-                
+
                 processed_img.put_pixel(x as u32, y as u32, *pixel);
             }
         }
-        
-        let offset_x1 = max_dx1 - anchor_x;
-        let offset_y1 = max_dy1 - anchor_y;
-        let offset_x2 = offset_x1 + new_width;
-        let offset_y2 = offset_y1 + new_height;
-        
-        let mut image_bytes = Vec::new();
-        processed_img
-            .write_to(&mut std::io::Cursor::new(&mut image_bytes), image::ImageFormat::Png)
-            .map_err(|e| PyValueError::new_err(e.to_string()))?;
-
-        frame_infos.push(ProcessedFrame {
-            image_bytes,
+
+        let anchor_x = anchor_x - x1;
+        let anchor_y = anchor_y - y1;
+        let offset_x1 = self.max_dx1 - anchor_x;
+        let offset_y1 = self.max_dy1 - anchor_y;
+
+        ProcessedFrame {
+            image: processed_img,
             offset_x1: offset_x1 as u32,
             offset_y1: offset_y1 as u32,
-            offset_x2: offset_x2 as u32,
-            offset_y2: offset_y2 as u32,
+            offset_x2: (offset_x1 + new_width) as u32,
+            offset_y2: (offset_y1 + new_height) as u32,
             anchor_x,
-            anchor_y
-        });
+            anchor_y,
+        }
     }
+}
 
-    Ok((frame_infos, (max_dx1 as u32, max_dy1 as u32), ((max_dx1 + max_dx2) as u32, (max_dy1 + max_dy2) as u32)))
+/// The bounds of the content of the image, padded to multiples of 4 around the anchor
+#[inline]
+fn layer_bounds(
+    img: &RgbaImage, (anchor_x, anchor_y): (i32, i32), is_empty: &impl Fn(&Rgba<u8>) -> bool
+) -> (i32, i32, i32, i32) {
+    let (x1, y1, x2, y2) = find_bounds(img, img.width(), img.height(), is_empty);
+    pad_bounds(x1, y1, x2, y2, anchor_x, anchor_y)
 }
 
 #[inline]
 fn find_bounds(
-    image: &ImageBuffer<Rgba<u8>, Vec<u8>>, width: u32, height: u32, format: &BcFormat
+    image: &ImageBuffer<Rgba<u8>, Vec<u8>>, width: u32, height: u32, is_empty: &impl Fn(&Rgba<u8>) -> bool
 ) -> (i32, i32, i32, i32) {
     let mut min_x = width;
     let mut min_y = height;
@@ -122,13 +347,7 @@ fn find_bounds(
 
     for y in 0..height {
         for x in 0..width {
-            let pixel = image.get_pixel(x, y);
-            let is_transparent = match format {
-                BcFormat::Bc1 => pixel[3] < 128,
-                BcFormat::Bc4 => pixel[0] == 0 && pixel[1] == 0 && pixel[2] == 0,
-                BcFormat::Bc7 => pixel[0] == 0 && pixel[1] == 0 && pixel[2] == 0 && pixel[3] == 0,
-            };
-            if !is_transparent {
+            if !is_empty(image.get_pixel(x, y)) {
                 min_x = min_x.min(x);
                 min_y = min_y.min(y);
                 max_x = max_x.max(x);
@@ -170,4 +389,102 @@ fn pad_bounds(x1: i32, y1: i32, x2: i32, y2: i32, anchor_x: i32, anchor_y: i32)
     let dy2 = round_to_4(dy2);
     
     (anchor_x - dx1, anchor_y - dy1, anchor_x + dx2, anchor_y + dy2)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An 8x8 frame anchored at its center with a single pixel at (5, 6)
+    fn single_pixel(pixel: [u8; 4]) -> Vec<(RgbaImage, (i32, i32))> {
+        let mut image = RgbaImage::new(8, 8);
+        image.put_pixel(5, 6, Rgba(pixel));
+        vec![(image, (4, 4))]
+    }
+
+    fn cropped_size(frames: &[ProcessedFrame]) -> (u32, u32) {
+        (frames[0].offset_x2 - frames[0].offset_x1, frames[0].offset_y2 - frames[0].offset_y1)
+    }
+
+    #[test]
+    fn crops_by_format() {
+        // a BC4 pixel that is black in the red channel and a BC7 pixel with zero alpha still have content
+        let (frames, _, _) = preprocess_images(single_pixel([0, 80, 0, 255]), BcFormat::Bc4, 128).unwrap();
+        assert_eq!(cropped_size(&frames), (4, 4));
+
+        let (frames, _, _) = preprocess_images(single_pixel([30, 0, 0, 0]), BcFormat::Bc7, 128).unwrap();
+        assert_eq!(cropped_size(&frames), (4, 4));
+
+        assert!(BcFormat::Bc1.is_empty(&Rgba([255, 255, 255, 99]), 100));
+        assert!(!BcFormat::Bc1.is_empty(&Rgba([0, 0, 0, 100]), 100));
+        // kept by cropping, but compressed as transparent
+        assert!(BcFormat::Bc7.is_transparent(&Rgba([30, 0, 0, 0]), 128));
+    }
+
+    #[test]
+    fn crops_by_layer_kind_and_custom_predicate() {
+        // transparent red at (1, 1) is shadow content, opaque black at (5, 6) is main layer content
+        let mut image = RgbaImage::new(8, 8);
+        image.put_pixel(1, 1, Rgba([200, 0, 0, 0]));
+        image.put_pixel(5, 6, Rgba([0, 0, 0, 255]));
+        let frames = vec![(image, (4, 4))];
+
+        let (main, _, _) = preprocess_images_with(frames.clone(), |pixel| {
+            LayerKind::Main.is_empty(pixel, 128)
+        }).unwrap();
+        assert_eq!((main[0].offset_x1, main[0].offset_y1, cropped_size(&main)), (0, 0, (4, 4)));
+        assert_eq!(main[0].image.get_pixel(1, 2), &Rgba([0, 0, 0, 255]));
+
+        let (shadow, hotspot, _) = preprocess_images_with(frames.clone(), |pixel| {
+            LayerKind::Shadow.is_empty(pixel, 128)
+        }).unwrap();
+        assert_eq!((hotspot, cropped_size(&shadow)), ((4, 4), (4, 4)));
+        assert_eq!(shadow[0].image.get_pixel(1, 1), &Rgba([200, 0, 0, 0]));
+
+        let (both, _, _) = preprocess_images_with(frames, |pixel| pixel.0 == [0; 4]).unwrap();
+        assert_eq!(cropped_size(&both), (8, 8));
+
+        assert!(!LayerKind::DamageMask.is_empty(&Rgba([0, 80, 0, 255]), 128));
+        assert!(LayerKind::PlayerColor.is_empty(&Rgba([0, 80, 0, 255]), 128));
+    }
+
+    #[test]
+    fn reports_image_errors() {
+        let path = std::env::temp_dir().join(format!("aoe2_tex_com_{}_not_an_image.png", std::process::id()));
+        std::fs::write(&path, b"not an image").unwrap();
+        let result = preprocess_frames(vec![(path.clone(), (0, 0))], BcFormat::Bc1, 128);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(Error::Image(_))));
+
+        let result = preprocess_frames(vec![(path, (0, 0))], BcFormat::Bc1, 128);
+        assert!(matches!(result, Err(Error::Io(..))));
+    }
+
+    #[test]
+    fn crops_masks_to_the_main_layer_and_shadows_to_their_own_bounds() {
+        let mut main = RgbaImage::new(8, 8);
+        main.put_pixel(5, 6, Rgba([0, 0, 0, 255]));
+        let mut shadow = RgbaImage::new(8, 8);
+        shadow.put_pixel(1, 1, Rgba([100, 100, 100, 255]));
+        // the mask pixel at (1, 1) is outside of the main layer's bounds
+        let mut mask = RgbaImage::new(8, 8);
+        mask.put_pixel(6, 5, Rgba([255, 255, 255, 255]));
+        mask.put_pixel(1, 1, Rgba([255, 255, 255, 255]));
+
+        let layers = FrameLayers::new(main, Some(shadow), Some(mask.clone()), Some(mask));
+        let (frames, hotspot, size) = preprocess_layer_images(vec![(layers, (4, 4))], 128).unwrap();
+        assert_eq!((hotspot, size), ((4, 4), (8, 8)));
+
+        let offsets = |frame: &ProcessedFrame| (frame.offset_x1, frame.offset_y1, frame.offset_x2, frame.offset_y2);
+        let frame = &frames[0];
+        assert_eq!(offsets(&frame.main), (4, 4, 8, 8));
+        assert_eq!(offsets(frame.shadow.as_ref().unwrap()), (0, 0, 4, 4));
+        assert_eq!(frame.shadow.as_ref().unwrap().image.get_pixel(1, 1), &Rgba([100, 100, 100, 255]));
+
+        for mask in [frame.player_color.as_ref().unwrap(), frame.damage_mask.as_ref().unwrap()] {
+            assert_eq!(offsets(mask), offsets(&frame.main));
+            assert_eq!(mask.image.get_pixel(2, 1), &Rgba([255, 255, 255, 255]));
+            assert_eq!(mask.image.pixels().filter(|pixel| pixel[0] != 0).count(), 1);
+        }
+    }
+}
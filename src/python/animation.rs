@@ -0,0 +1,82 @@
+use pyo3::prelude::*;
+use rayon::prelude::*;
+use crate::animation::{LayerDecoder, LayerEncoder};
+use crate::encode_decode::EncodeOptions;
+use crate::format::{BcFormat, BcQuality, DrawCall};
+use crate::preprocess::ProcessedFrame;
+use crate::python::to_png;
+use crate::sld::SldLayer;
+
+#[pymethods]
+impl LayerDecoder {
+    #[new]
+    fn py_new() -> Self {
+        Self::new()
+    }
+
+    #[pyo3(name = "reset")]
+    fn py_reset(&mut self) {
+        self.reset()
+    }
+
+    #[pyo3(name = "decode", signature = (bytes, offsets, format, commands, use_prev = false))]
+    fn py_decode(
+        &mut self,
+        py: Python<'_>,
+        bytes: Vec<u8>,
+        offsets: (u32, u32, u32, u32),
+        format: BcFormat,
+        commands: Vec<DrawCall>,
+        use_prev: bool,
+    ) -> PyResult<Vec<u8>> {
+        py.detach(|| to_png(self.decode(bytes, offsets, format, &commands, use_prev)?))
+    }
+
+    #[pyo3(name = "decode_layer")]
+    fn py_decode_layer(&mut self, py: Python<'_>, layer: &SldLayer) -> PyResult<Vec<u8>> {
+        py.detach(|| to_png(self.decode_layer(layer)?))
+    }
+}
+
+#[pyfunction]
+pub fn decode_animation(py: Python<'_>, layers: Vec<SldLayer>) -> PyResult<Vec<Vec<u8>>> {
+    py.detach(|| {
+        crate::animation::decode_animation(&layers)?
+            .into_par_iter()
+            .map(to_png)
+            .collect()
+    })
+}
+
+#[pymethods]
+impl LayerEncoder {
+    #[new]
+    #[pyo3(signature = (format = BcFormat::Bc1, quality = BcQuality::Slow, options = None))]
+    fn py_new(format: BcFormat, quality: BcQuality, options: Option<EncodeOptions>) -> Self {
+        Self::new(format, quality, options.unwrap_or_default())
+    }
+
+    #[pyo3(name = "reset")]
+    fn py_reset(&mut self) {
+        self.reset()
+    }
+
+    #[pyo3(name = "encode")]
+    fn py_encode(&mut self, py: Python<'_>, frame: &ProcessedFrame) -> PyResult<(Vec<DrawCall>, Vec<u8>, bool)> {
+        Ok(py.detach(|| self.encode(frame))?)
+    }
+}
+
+#[allow(clippy::type_complexity)]
+#[pyfunction]
+#[pyo3(signature = (frames, format = BcFormat::Bc1, quality = BcQuality::Slow, options = None))]
+pub fn encode_animation(
+    py: Python<'_>,
+    frames: Vec<ProcessedFrame>,
+    format: BcFormat,
+    quality: BcQuality,
+    options: Option<EncodeOptions>,
+) -> PyResult<Vec<(Vec<DrawCall>, Vec<u8>, bool)>> {
+    let options = options.unwrap_or_default();
+    Ok(py.detach(|| crate::animation::encode_animation(&frames, format, quality, &options))?)
+}
@@ -0,0 +1,67 @@
+use pyo3::prelude::*;
+use rayon::prelude::*;
+use crate::encode_decode::EncodeOptions;
+use crate::format::{BcFormat, BcQuality, DrawCall};
+use crate::preprocess::ProcessedFrame;
+use crate::python::{load_image, to_png};
+use crate::sld::SldLayer;
+
+#[allow(clippy::type_complexity)]
+#[pyfunction]
+#[pyo3(signature = (images, format = BcFormat::Bc1, quality = BcQuality::Slow, gen_commands = false, options = None))]
+pub fn encode_batch(
+    py: Python<'_>,
+    images: Vec<Vec<u8>>,
+    format: BcFormat,
+    quality: BcQuality,
+    gen_commands: bool,
+    options: Option<EncodeOptions>,
+) -> PyResult<Vec<(Vec<u8>, Option<(Vec<DrawCall>, Vec<u8>)>)>> {
+    let options = options.unwrap_or_default();
+    py.detach(|| {
+        let images = images.par_iter()
+            .enumerate()
+            .map(|(i, bytes)| load_image(bytes, &format!("image {i}")).map(|img| img.to_rgba8()))
+            .collect::<PyResult<Vec<_>>>()?;
+
+        Ok(crate::batch::encode_batch(&images, format, quality, gen_commands, &options)?)
+    })
+}
+
+#[allow(clippy::type_complexity)]
+#[pyfunction]
+pub fn decode_batch(
+    py: Python<'_>,
+    frames: Vec<(Vec<u8>, u32, u32, BcFormat, Option<Vec<DrawCall>>)>,
+) -> PyResult<Vec<(Vec<u8>, Vec<u8>)>> {
+    py.detach(|| {
+        crate::batch::decode_batch(frames)?
+            .into_par_iter()
+            .map(|(image, data)| Ok((to_png(image)?, data)))
+            .collect()
+    })
+}
+
+#[allow(clippy::type_complexity)]
+#[pyfunction]
+#[pyo3(signature = (animations, format = BcFormat::Bc1, quality = BcQuality::Slow, options = None))]
+pub fn encode_animations(
+    py: Python<'_>,
+    animations: Vec<Vec<ProcessedFrame>>,
+    format: BcFormat,
+    quality: BcQuality,
+    options: Option<EncodeOptions>,
+) -> PyResult<Vec<Vec<(Vec<DrawCall>, Vec<u8>, bool)>>> {
+    let options = options.unwrap_or_default();
+    Ok(py.detach(|| crate::batch::encode_animations(&animations, format, quality, &options))?)
+}
+
+#[pyfunction]
+pub fn decode_animations(py: Python<'_>, animations: Vec<Vec<SldLayer>>) -> PyResult<Vec<Vec<Vec<u8>>>> {
+    py.detach(|| {
+        crate::batch::decode_animations(&animations)?
+            .into_par_iter()
+            .map(|images| images.into_iter().map(to_png).collect())
+            .collect()
+    })
+}
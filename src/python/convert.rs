@@ -0,0 +1,36 @@
+use pyo3::prelude::*;
+use crate::encode_decode::EncodeOptions;
+use crate::format::BcQuality;
+use crate::sld::SldFile;
+use crate::slp::SlpFile;
+use crate::smx::SmxFile;
+
+#[pyfunction]
+#[pyo3(signature = (slp, palette, player = 1, quality = BcQuality::Slow, options = None))]
+pub fn slp_to_sld(
+    py: Python<'_>,
+    slp: &SlpFile,
+    palette: Vec<[u8; 3]>,
+    player: u8,
+    quality: BcQuality,
+    options: Option<EncodeOptions>,
+) -> PyResult<SldFile> {
+    let options = options.unwrap_or_default();
+    Ok(py.detach(|| crate::convert::slp_to_sld(slp, &palette, player, quality, &options))?)
+}
+
+#[pyfunction]
+#[pyo3(signature = (smx, palette, player_palette = None, quality = BcQuality::Slow, options = None))]
+pub fn smx_to_sld(
+    py: Python<'_>,
+    smx: &SmxFile,
+    palette: Vec<[u8; 3]>,
+    player_palette: Option<Vec<[u8; 3]>>,
+    quality: BcQuality,
+    options: Option<EncodeOptions>,
+) -> PyResult<SldFile> {
+    let options = options.unwrap_or_default();
+    Ok(py.detach(|| {
+        crate::convert::smx_to_sld(smx, &palette, player_palette.as_deref(), quality, &options)
+    })?)
+}
@@ -0,0 +1,45 @@
+use pyo3::prelude::*;
+use crate::encode_decode::EncodeOptions;
+use crate::format::{BcFormat, BcQuality};
+use crate::python::{load_image, to_png};
+
+#[pyfunction]
+#[pyo3(signature = (blocks, width, height, format, mipmaps = 1))]
+pub fn to_dds(blocks: &[u8], width: u32, height: u32, format: BcFormat, mipmaps: u32) -> PyResult<Vec<u8>> {
+    Ok(crate::dds::to_dds(blocks, width, height, format, mipmaps)?)
+}
+
+#[pyfunction]
+#[pyo3(signature = (bytes, format = BcFormat::Bc1, quality = BcQuality::Slow, mipmaps = false, options = None))]
+pub fn encode_dds(
+    py: Python<'_>,
+    bytes: &[u8],
+    format: BcFormat,
+    quality: BcQuality,
+    mipmaps: bool,
+    options: Option<EncodeOptions>,
+) -> PyResult<Vec<u8>> {
+    let options = options.unwrap_or_default();
+    py.detach(|| {
+        let image = load_image(bytes, "input")?.to_rgba8();
+        Ok(crate::dds::encode_dds(&image, format, quality, mipmaps, &options)?)
+    })
+}
+
+#[pyfunction]
+pub fn decode_dds(py: Python<'_>, bytes: &[u8]) -> PyResult<(Vec<u8>, BcFormat, Vec<u8>)> {
+    py.detach(|| {
+        let (image, format, blocks) = crate::dds::decode_dds(bytes)?;
+        Ok((to_png(image)?, format, blocks))
+    })
+}
+
+#[allow(clippy::type_complexity)]
+#[pyfunction]
+pub fn decode_dds_raw(py: Python<'_>, bytes: &[u8]) -> PyResult<(Vec<u8>, u32, u32, BcFormat, Vec<u8>)> {
+    py.detach(|| {
+        let (image, format, blocks) = crate::dds::decode_dds(bytes)?;
+        let (width, height) = image.dimensions();
+        Ok((image.into_raw(), width, height, format, blocks))
+    })
+}
@@ -0,0 +1,113 @@
+use image::RgbaImage;
+use pyo3::buffer::PyBuffer;
+use pyo3::prelude::*;
+use crate::encode_decode::EncodeOptions;
+use crate::format::{AlphaDither, BcFormat, BcQuality, DrawCall, SkipMode, DEFAULT_ALPHA_CUTOFF};
+use crate::python::{buffer_slice, load_image, to_png};
+
+#[pymethods]
+impl EncodeOptions {
+    #[new]
+    #[pyo3(signature = (
+        skip_mode = SkipMode::Bitwise, max_delta = None, max_mse = None, alpha_cutoff = DEFAULT_ALPHA_CUTOFF,
+        alpha_dither = AlphaDither::Threshold, bleed_colors = false
+    ))]
+    fn py_new(
+        skip_mode: SkipMode,
+        max_delta: Option<u8>,
+        max_mse: Option<f32>,
+        alpha_cutoff: u8,
+        alpha_dither: AlphaDither,
+        bleed_colors: bool,
+    ) -> Self {
+        Self { skip_mode, max_delta, max_mse, alpha_cutoff, alpha_dither, bleed_colors }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+#[pyfunction]
+#[pyo3(signature = (
+    bytes, format = BcFormat::Bc1, quality = BcQuality::Slow, gen_commands = false, prev_info = None, options = None
+))]
+pub fn encode(
+    py: Python<'_>,
+    bytes: &[u8],
+    format: BcFormat,
+    quality: BcQuality,
+    gen_commands: bool,
+    prev_info: Option<(&[u8], u32, u32, i32, i32)>,
+    options: Option<EncodeOptions>,
+) -> PyResult<(Vec<u8>, Option<(Vec<DrawCall>, Vec<u8>)>)> {
+    let options = options.unwrap_or_default();
+    py.detach(|| {
+        let image = load_image(bytes, "input")?.to_rgba8();
+        Ok(crate::encode_decode::encode(&image, format, quality, gen_commands, prev_info, &options)?)
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (
+    bytes, width, height, format, commands = None, prev_info = None
+))]
+pub fn decode(
+    py: Python<'_>,
+    bytes: Vec<u8>,
+    width: u32,
+    height: u32,
+    format: BcFormat,
+    commands: Option<Vec<DrawCall>>,
+    prev_info: Option<(&[u8], u32, u32, i32, i32)>,
+) -> PyResult<(Vec<u8>, Vec<u8>)> {
+    py.detach(|| {
+        let (image, data) = crate::encode_decode::decode(
+            bytes, width, height, format, commands.as_deref(), prev_info
+        )?;
+        Ok((to_png(image)?, data))
+    })
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (
+    pixels, width, height, format = BcFormat::Bc1, quality = BcQuality::Slow, gen_commands = false, prev_info = None,
+    options = None
+))]
+pub fn encode_raw(
+    py: Python<'_>,
+    pixels: PyBuffer<u8>,
+    width: u32,
+    height: u32,
+    format: BcFormat,
+    quality: BcQuality,
+    gen_commands: bool,
+    prev_info: Option<(&[u8], u32, u32, i32, i32)>,
+    options: Option<EncodeOptions>,
+) -> PyResult<(Vec<u8>, Option<(Vec<DrawCall>, Vec<u8>)>)> {
+    let pixels = buffer_slice(py, &pixels, (width, height), 4, "pixels")?;
+    let image = RgbaImage::from_raw(width, height, pixels.to_vec()).unwrap();
+    let options = options.unwrap_or_default();
+    Ok(py.detach(|| {
+        crate::encode_decode::encode(&image, format, quality, gen_commands, prev_info, &options)
+    })?)
+}
+
+#[pyfunction]
+#[pyo3(signature = (
+    bytes, width, height, format, commands = None, prev_info = None
+))]
+pub fn decode_raw(
+    py: Python<'_>,
+    bytes: Vec<u8>,
+    width: u32,
+    height: u32,
+    format: BcFormat,
+    commands: Option<Vec<DrawCall>>,
+    prev_info: Option<(&[u8], u32, u32, i32, i32)>,
+) -> PyResult<(Vec<u8>, Vec<u8>)> {
+    py.detach(|| {
+        let (image, data) = crate::encode_decode::decode(
+            bytes, width, height, format, commands.as_deref(), prev_info
+        )?;
+        Ok((image.into_raw(), data))
+    })
+}
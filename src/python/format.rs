@@ -0,0 +1,10 @@
+use pyo3::prelude::*;
+use crate::format::DrawCall;
+
+#[pymethods]
+impl DrawCall {
+    #[new]
+    fn py_new(skip: u8, draw: u8) -> Self {
+        Self::new(skip, draw)
+    }
+}
@@ -0,0 +1,37 @@
+use pyo3::prelude::*;
+use crate::encode_decode::EncodeOptions;
+use crate::format::{BcFormat, BcQuality, DEFAULT_ALPHA_CUTOFF};
+use crate::metrics::QualityMetrics;
+use crate::python::load_image;
+
+#[pyfunction]
+#[pyo3(signature = (bytes, format = BcFormat::Bc1, quality = BcQuality::Slow, options = None))]
+pub fn measure_quality(
+    py: Python<'_>,
+    bytes: &[u8],
+    format: BcFormat,
+    quality: BcQuality,
+    options: Option<EncodeOptions>,
+) -> PyResult<QualityMetrics> {
+    let options = options.unwrap_or_default();
+    py.detach(|| {
+        let image = load_image(bytes, "input")?.to_rgba8();
+        Ok(crate::metrics::measure_quality(&image, format, quality, &options)?)
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (original, decoded, format = BcFormat::Bc1, alpha_cutoff = DEFAULT_ALPHA_CUTOFF))]
+pub fn compare_images(
+    py: Python<'_>,
+    original: &[u8],
+    decoded: &[u8],
+    format: BcFormat,
+    alpha_cutoff: u8,
+) -> PyResult<QualityMetrics> {
+    py.detach(|| {
+        let original = load_image(original, "original")?.to_rgba8();
+        let decoded = load_image(decoded, "decoded")?.to_rgba8();
+        Ok(crate::metrics::compare_images(&original, &decoded, format, alpha_cutoff)?)
+    })
+}
@@ -0,0 +1,120 @@
+mod format;
+mod encode_decode;
+mod preprocess;
+mod render;
+mod palette;
+mod sld;
+mod slp;
+mod smx;
+mod animation;
+mod batch;
+mod metrics;
+mod convert;
+mod dds;
+
+use image::{DynamicImage, ImageFormat};
+use pyo3::buffer::PyBuffer;
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use crate::encode_decode::EncodeOptions;
+use crate::format::{AlphaDither, BcFormat, BcQuality, DrawCall, SkipMode};
+use crate::preprocess::{LayerKind, ProcessedFrame};
+use crate::animation::{LayerDecoder, LayerEncoder};
+use crate::metrics::QualityMetrics;
+use crate::palette::Palette;
+use crate::sld::{SldFile, SldFrame, SldHeader, SldLayer, SldLayerHeader};
+use crate::slp::{DecodedSlpFrame, SlpFile, SlpFrame, SlpHeader};
+use crate::smx::{DecodedSmxFrame, SmxFile, SmxFrame, SmxHeader, SmxLayer};
+
+#[pymodule]
+#[pyo3(name = "aoe2_tex_com")]
+fn aoe2_tex_com(pid: &Bound<PyModule>) -> PyResult<()> {
+    pid.add_class::<BcFormat>()?;
+    pid.add_class::<BcQuality>()?;
+    pid.add_class::<SkipMode>()?;
+    pid.add_class::<AlphaDither>()?;
+    pid.add_class::<EncodeOptions>()?;
+    pid.add_class::<ProcessedFrame>()?;
+    pid.add_class::<LayerKind>()?;
+    pid.add_class::<DrawCall>()?;
+    pid.add_class::<SldFile>()?;
+    pid.add_class::<SldHeader>()?;
+    pid.add_class::<SldFrame>()?;
+    pid.add_class::<SldLayer>()?;
+    pid.add_class::<SldLayerHeader>()?;
+    pid.add_class::<SlpFile>()?;
+    pid.add_class::<SlpHeader>()?;
+    pid.add_class::<SlpFrame>()?;
+    pid.add_class::<DecodedSlpFrame>()?;
+    pid.add_class::<SmxFile>()?;
+    pid.add_class::<SmxHeader>()?;
+    pid.add_class::<SmxFrame>()?;
+    pid.add_class::<SmxLayer>()?;
+    pid.add_class::<DecodedSmxFrame>()?;
+    pid.add_class::<LayerDecoder>()?;
+    pid.add_class::<LayerEncoder>()?;
+    pid.add_class::<QualityMetrics>()?;
+    pid.add_class::<Palette>()?;
+
+    pid.add_function(wrap_pyfunction!(encode_decode::encode, pid)?)?;
+    pid.add_function(wrap_pyfunction!(encode_decode::decode, pid)?)?;
+    pid.add_function(wrap_pyfunction!(encode_decode::encode_raw, pid)?)?;
+    pid.add_function(wrap_pyfunction!(encode_decode::decode_raw, pid)?)?;
+    pid.add_function(wrap_pyfunction!(preprocess::preprocess_frames, pid)?)?;
+    pid.add_function(wrap_pyfunction!(preprocess::preprocess_raw, pid)?)?;
+    pid.add_function(wrap_pyfunction!(preprocess::preprocess_layers, pid)?)?;
+    pid.add_function(wrap_pyfunction!(preprocess::preprocess_layers_raw, pid)?)?;
+    pid.add_function(wrap_pyfunction!(render::render_frames, pid)?)?;
+    pid.add_function(wrap_pyfunction!(render::render_raw, pid)?)?;
+    pid.add_function(wrap_pyfunction!(animation::decode_animation, pid)?)?;
+    pid.add_function(wrap_pyfunction!(animation::encode_animation, pid)?)?;
+    pid.add_function(wrap_pyfunction!(batch::encode_batch, pid)?)?;
+    pid.add_function(wrap_pyfunction!(batch::decode_batch, pid)?)?;
+    pid.add_function(wrap_pyfunction!(batch::encode_animations, pid)?)?;
+    pid.add_function(wrap_pyfunction!(batch::decode_animations, pid)?)?;
+    pid.add_function(wrap_pyfunction!(metrics::measure_quality, pid)?)?;
+    pid.add_function(wrap_pyfunction!(metrics::compare_images, pid)?)?;
+    pid.add_function(wrap_pyfunction!(convert::slp_to_sld, pid)?)?;
+    pid.add_function(wrap_pyfunction!(convert::smx_to_sld, pid)?)?;
+    pid.add_function(wrap_pyfunction!(dds::to_dds, pid)?)?;
+    pid.add_function(wrap_pyfunction!(dds::encode_dds, pid)?)?;
+    pid.add_function(wrap_pyfunction!(dds::decode_dds, pid)?)?;
+    pid.add_function(wrap_pyfunction!(dds::decode_dds_raw, pid)?)?;
+    
+    Ok(())
+}
+
+fn load_image(bytes: &[u8], name: &str) -> PyResult<DynamicImage> {
+    image::load_from_memory(bytes)
+        .map_err(|e| PyValueError::new_err(format!("Failed to load {name} image: {}", e)))
+}
+
+fn to_png(image: impl Into<DynamicImage>) -> PyResult<Vec<u8>> {
+    let mut png_bytes = Vec::new();
+    image.into().write_to(&mut std::io::Cursor::new(&mut png_bytes), ImageFormat::Png)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(png_bytes)
+}
+
+/// Views a buffer holding `width * height * channels` bytes without copying it
+fn buffer_slice<'py>(
+    _py: Python<'py>,
+    buffer: &'py PyBuffer<u8>,
+    (width, height): (u32, u32),
+    channels: usize,
+    name: &str,
+) -> PyResult<&'py [u8]> {
+    let len = width as usize * height as usize * channels;
+    if !buffer.is_c_contiguous() {
+        return Err(PyValueError::new_err(format!("The {name} buffer must be C-contiguous")));
+    }
+    if buffer.item_count() != len {
+        return Err(PyValueError::new_err(format!(
+            "The {name} buffer has {} bytes but {width}x{height} pixels with {channels} channels need {len} bytes",
+            buffer.item_count()
+        )));
+    }
+    // SAFETY: the buffer is C-contiguous and holds `len` bytes. Its exporter keeps the memory alive and cannot resize
+    // it while `buffer` is held, and holding the GIL for 'py prevents python code from mutating it meanwhile
+    Ok(unsafe { std::slice::from_raw_parts(buffer.buf_ptr() as *const u8, len) })
+}
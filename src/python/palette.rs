@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+use pyo3::prelude::*;
+use crate::palette::Palette;
+
+#[pymethods]
+impl Palette {
+    #[new]
+    fn py_new(colors: Vec<[u8; 3]>) -> Self {
+        Self::new(colors)
+    }
+
+    #[getter]
+    fn colors(&self) -> Vec<(u8, u8, u8)> {
+        self.colors.iter().map(|&[r, g, b]| (r, g, b)).collect()
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_bytes")]
+    fn py_from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        Ok(Self::from_bytes(bytes)?)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_file")]
+    fn py_from_file(path: PathBuf) -> PyResult<Self> {
+        Ok(Self::from_file(path)?)
+    }
+
+    #[pyo3(name = "to_bytes")]
+    fn py_to_bytes(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
+
+    #[pyo3(name = "to_file")]
+    fn py_to_file(&self, path: PathBuf) -> PyResult<()> {
+        Ok(self.to_file(path)?)
+    }
+
+    #[pyo3(name = "color")]
+    fn py_color(&self, index: usize) -> PyResult<(u8, u8, u8)> {
+        Ok(self.color(index)?)
+    }
+
+    fn __len__(&self) -> usize {
+        self.colors.len()
+    }
+}
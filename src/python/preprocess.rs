@@ -0,0 +1,196 @@
+use std::cell::RefCell;
+use std::path::PathBuf;
+use image::{Rgba, RgbaImage};
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use crate::error::Result;
+use crate::format::{BcFormat, DEFAULT_ALPHA_CUTOFF};
+use crate::preprocess::{FrameLayers, LayerKind, ProcessedFrame};
+use crate::python::{buffer_slice, to_png};
+
+#[pymethods]
+impl ProcessedFrame {
+    #[getter]
+    fn image_bytes(&self) -> PyResult<Vec<u8>> {
+        to_png(self.image.clone())
+    }
+
+    /// The RGBA pixels of the processed image, its width and height are given by the offsets
+    #[getter]
+    fn image_raw(&self) -> Vec<u8> {
+        self.image.as_raw().clone()
+    }
+
+    #[getter]
+    fn offset_x1(&self) -> u32 {
+        self.offset_x1
+    }
+
+    #[getter]
+    fn offset_y1(&self) -> u32 {
+        self.offset_y1
+    }
+
+    #[getter]
+    fn offset_x2(&self) -> u32 {
+        self.offset_x2
+    }
+
+    #[getter]
+    fn offset_y2(&self) -> u32 {
+        self.offset_y2
+    }
+
+    #[getter]
+    fn anchor_x(&self) -> i32 {
+        self.anchor_x
+    }
+
+    #[getter]
+    fn anchor_y(&self) -> i32 {
+        self.anchor_y
+    }
+}
+
+#[allow(clippy::type_complexity)]
+#[pyfunction]
+#[pyo3(name = "preprocess", signature = (
+    frame_info, format = BcFormat::Bc1, alpha_cutoff = DEFAULT_ALPHA_CUTOFF, layer_kind = None, is_empty = None
+))]
+pub fn preprocess_frames(
+    py: Python<'_>,
+    frame_info: Vec<(PathBuf, (i32, i32))>,
+    format: BcFormat,
+    alpha_cutoff: u8,
+    layer_kind: Option<LayerKind>,
+    is_empty: Option<Py<PyAny>>,
+) -> PyResult<(Vec<ProcessedFrame>, (u32, u32), (u32, u32))> {
+    with_predicate(py, format, alpha_cutoff, layer_kind, is_empty, |is_empty| {
+        crate::preprocess::preprocess_frames_with(frame_info, is_empty)
+    })
+}
+
+#[allow(clippy::type_complexity)]
+#[pyfunction]
+#[pyo3(signature = (
+    frame_info, format = BcFormat::Bc1, alpha_cutoff = DEFAULT_ALPHA_CUTOFF, layer_kind = None, is_empty = None
+))]
+pub fn preprocess_raw(
+    py: Python<'_>,
+    frame_info: Vec<(PyBuffer<u8>, (u32, u32), (i32, i32))>,
+    format: BcFormat,
+    alpha_cutoff: u8,
+    layer_kind: Option<LayerKind>,
+    is_empty: Option<Py<PyAny>>,
+) -> PyResult<(Vec<ProcessedFrame>, (u32, u32), (u32, u32))> {
+    let frames = frame_info.iter()
+        .map(|(pixels, (width, height), anchor)| {
+            let pixels = buffer_slice(py, pixels, (*width, *height), 4, "pixels")?;
+            Ok((RgbaImage::from_raw(*width, *height, pixels.to_vec()).unwrap(), *anchor))
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    with_predicate(py, format, alpha_cutoff, layer_kind, is_empty, |is_empty| {
+        crate::preprocess::preprocess_images_with(frames, is_empty)
+    })
+}
+
+/// The processed (main, shadow, player color, damage mask) layers of a frame
+type ProcessedLayers = (ProcessedFrame, Option<ProcessedFrame>, Option<ProcessedFrame>, Option<ProcessedFrame>);
+
+#[allow(clippy::type_complexity)]
+#[pyfunction]
+#[pyo3(signature = (frame_info, alpha_cutoff = DEFAULT_ALPHA_CUTOFF))]
+pub fn preprocess_layers(
+    py: Python<'_>,
+    frame_info: Vec<((PathBuf, Option<PathBuf>, Option<PathBuf>, Option<PathBuf>), (i32, i32))>,
+    alpha_cutoff: u8,
+) -> PyResult<(Vec<ProcessedLayers>, (u32, u32), (u32, u32))> {
+    let frame_info = frame_info.into_iter()
+        .map(|((main, shadow, player_color, damage_mask), anchor)| {
+            (FrameLayers::new(main, shadow, player_color, damage_mask), anchor)
+        })
+        .collect();
+
+    let (layers, hotspot, size) = py.detach(|| {
+        crate::preprocess::preprocess_layer_frames(frame_info, alpha_cutoff)
+    })?;
+    Ok((layers.into_iter().map(to_tuple).collect(), hotspot, size))
+}
+
+#[allow(clippy::type_complexity)]
+#[pyfunction]
+#[pyo3(signature = (frame_info, alpha_cutoff = DEFAULT_ALPHA_CUTOFF))]
+pub fn preprocess_layers_raw(
+    py: Python<'_>,
+    frame_info: Vec<(
+        (PyBuffer<u8>, Option<PyBuffer<u8>>, Option<PyBuffer<u8>>, Option<PyBuffer<u8>>),
+        (u32, u32),
+        (i32, i32),
+    )>,
+    alpha_cutoff: u8,
+) -> PyResult<(Vec<ProcessedLayers>, (u32, u32), (u32, u32))> {
+    let frames = frame_info.iter()
+        .map(|((main, shadow, player_color, damage_mask), (width, height), anchor)| {
+            let image = |pixels: &PyBuffer<u8>, name: &str| -> PyResult<RgbaImage> {
+                let pixels = buffer_slice(py, pixels, (*width, *height), 4, name)?;
+                Ok(RgbaImage::from_raw(*width, *height, pixels.to_vec()).unwrap())
+            };
+
+            let layers = FrameLayers::new(
+                image(main, "main")?,
+                shadow.as_ref().map(|pixels| image(pixels, "shadow")).transpose()?,
+                player_color.as_ref().map(|pixels| image(pixels, "player_color")).transpose()?,
+                damage_mask.as_ref().map(|pixels| image(pixels, "damage_mask")).transpose()?,
+            );
+            Ok((layers, *anchor))
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let (layers, hotspot, size) = py.detach(|| {
+        crate::preprocess::preprocess_layer_images(frames, alpha_cutoff)
+    })?;
+    Ok((layers.into_iter().map(to_tuple).collect(), hotspot, size))
+}
+
+fn to_tuple(layers: FrameLayers<ProcessedFrame>) -> ProcessedLayers {
+    (layers.main, layers.shadow, layers.player_color, layers.damage_mask)
+}
+
+/// Runs `f` with the emptiness predicate given by a python callable taking (r, g, b, a), a layer kind or otherwise the
+/// format. The GIL is released unless a python callable has to be called
+fn with_predicate<T: Send>(
+    py: Python<'_>,
+    format: BcFormat,
+    alpha_cutoff: u8,
+    layer_kind: Option<LayerKind>,
+    is_empty: Option<Py<PyAny>>,
+    f: impl FnOnce(&dyn Fn(&Rgba<u8>) -> bool) -> Result<T> + Send,
+) -> PyResult<T> {
+    match (layer_kind, is_empty) {
+        (Some(_), Some(_)) => Err(PyValueError::new_err("Only one of layer_kind and is_empty can be given")),
+        (Some(kind), None) => Ok(py.detach(|| f(&|pixel| kind.is_empty(pixel, alpha_cutoff)))?),
+        (None, None) => Ok(py.detach(|| f(&|pixel| format.is_empty(pixel, alpha_cutoff)))?),
+        (None, Some(is_empty)) => {
+            // the first error raised by the callable is kept and reported once preprocessing is done
+            let error = RefCell::new(None);
+            let result = f(&|pixel| {
+                if error.borrow().is_some() {
+                    return false;
+                }
+                is_empty.call1(py, (pixel[0], pixel[1], pixel[2], pixel[3]))
+                    .and_then(|empty| empty.extract::<bool>(py))
+                    .unwrap_or_else(|e| {
+                        *error.borrow_mut() = Some(e);
+                        false
+                    })
+            });
+
+            match error.into_inner() {
+                Some(e) => Err(e),
+                None => Ok(result?),
+            }
+        }
+    }
+}
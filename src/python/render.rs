@@ -0,0 +1,119 @@
+use image::{GrayImage, RgbaImage};
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use crate::palette::Palette;
+use crate::python::{buffer_slice, load_image, to_png};
+use crate::render::{PlayerColor, RenderOptions};
+
+#[pyfunction]
+#[pyo3(name = "render", signature = (
+    main_layer, shadow_layer, shadow_layer_offset, player_color_mask, damage_mask = None, color = (255, 0, 0),
+    shadow_color = (0, 0, 0), shadow_opacity = 1.0, palette = None, player = None
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn render_frames(
+    py: Python<'_>,
+    main_layer: &[u8],
+    shadow_layer: &[u8],
+    shadow_layer_offset: (i32, i32),
+    player_color_mask: &[u8],
+    damage_mask: Option<(&[u8], u32)>,
+    color: (u8, u8, u8),
+    shadow_color: (u8, u8, u8),
+    shadow_opacity: f32,
+    palette: Option<Palette>,
+    player: Option<usize>,
+) -> PyResult<Vec<u8>> {
+    let color = player_color(color, palette, player)?;
+    py.detach(|| {
+        let main_layer = load_image(main_layer, "shading")?.to_rgba8();
+        let shadow_layer = load_image(shadow_layer, "shadow")?.to_luma8();
+        let player_color_mask = load_image(player_color_mask, "mask")?.to_luma8();
+
+        let damage_mask = damage_mask
+            .map(|(damage_mask, percent)| {
+                load_image(damage_mask, "damage_mask").map(|img| (img.to_rgba8(), percent))
+            })
+            .transpose()?;
+
+        let options = RenderOptions { color, shadow_color, shadow_opacity };
+
+        let output = crate::render::render_frames(
+            &main_layer,
+            &shadow_layer,
+            shadow_layer_offset,
+            &player_color_mask,
+            damage_mask.as_ref().map(|(damage_mask, percent)| (damage_mask, *percent)),
+            &options,
+        )?;
+
+        to_png(output)
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (
+    main_layer, main_size, shadow_layer, shadow_size, shadow_layer_offset, player_color_mask, damage_mask = None,
+    color = (255, 0, 0), shadow_color = (0, 0, 0), shadow_opacity = 1.0, palette = None, player = None
+))]
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn render_raw(
+    py: Python<'_>,
+    main_layer: PyBuffer<u8>,
+    main_size: (u32, u32),
+    shadow_layer: PyBuffer<u8>,
+    shadow_size: (u32, u32),
+    shadow_layer_offset: (i32, i32),
+    player_color_mask: PyBuffer<u8>,
+    damage_mask: Option<(PyBuffer<u8>, u32)>,
+    color: (u8, u8, u8),
+    shadow_color: (u8, u8, u8),
+    shadow_opacity: f32,
+    palette: Option<Palette>,
+    player: Option<usize>,
+) -> PyResult<(Vec<u8>, (u32, u32))> {
+    let color = player_color(color, palette, player)?;
+    let (width, height) = main_size;
+    let (shadow_width, shadow_height) = shadow_size;
+
+    let main_layer = buffer_slice(py, &main_layer, main_size, 4, "main layer")?;
+    let main_layer = RgbaImage::from_raw(width, height, main_layer.to_vec()).unwrap();
+
+    let shadow_layer = buffer_slice(py, &shadow_layer, shadow_size, 1, "shadow layer")?;
+    let shadow_layer = GrayImage::from_raw(shadow_width, shadow_height, shadow_layer.to_vec()).unwrap();
+
+    let player_color_mask = buffer_slice(py, &player_color_mask, main_size, 1, "player color mask")?;
+    let player_color_mask = GrayImage::from_raw(width, height, player_color_mask.to_vec()).unwrap();
+
+    let damage_mask = damage_mask
+        .map(|(damage_mask, percent)| {
+            buffer_slice(py, &damage_mask, main_size, 4, "damage mask")
+                .map(|pixels| (RgbaImage::from_raw(width, height, pixels.to_vec()).unwrap(), percent))
+        })
+        .transpose()?;
+
+    let options = RenderOptions { color, shadow_color, shadow_opacity };
+
+    let output = py.detach(|| crate::render::render_frames(
+        &main_layer,
+        &shadow_layer,
+        shadow_layer_offset,
+        &player_color_mask,
+        damage_mask.as_ref().map(|(damage_mask, percent)| (damage_mask, *percent)),
+        &options,
+    ))?;
+
+    let size = output.dimensions();
+    Ok((output.into_raw(), size))
+}
+
+/// The player color given by a palette and player index, a player color palette or otherwise `color`
+fn player_color(color: (u8, u8, u8), palette: Option<Palette>, player: Option<usize>) -> PyResult<PlayerColor> {
+    match (palette, player) {
+        (Some(palette), Some(player)) => Ok(PlayerColor::Indexed(palette, player)),
+        (Some(palette), None) => Ok(PlayerColor::Palette(palette)),
+        (None, Some(_)) => Err(PyValueError::new_err("A player index requires a palette")),
+        (None, None) => Ok(color.into()),
+    }
+}
@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+use pyo3::prelude::*;
+use crate::format::{BcFormat, DrawCall};
+use crate::preprocess::ProcessedFrame;
+use crate::python::to_png;
+use crate::sld::{SldFile, SldFrame, SldLayer, SldLayerHeader, SLD_VERSION};
+
+#[pymethods]
+impl SldLayerHeader {
+    #[new]
+    #[pyo3(signature = (offset_x1, offset_y1, offset_x2, offset_y2, flag1, unknown1 = 0))]
+    fn py_new(offset_x1: u16, offset_y1: u16, offset_x2: u16, offset_y2: u16, flag1: u8, unknown1: u8) -> Self {
+        Self::new(offset_x1, offset_y1, offset_x2, offset_y2, flag1, unknown1)
+    }
+}
+
+#[pymethods]
+impl SldLayer {
+    #[new]
+    fn py_new(header: SldLayerHeader, commands: Vec<DrawCall>, blocks: Vec<u8>) -> Self {
+        Self::new(header, commands, blocks)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_encoded", signature = (encoded, frame, format = BcFormat::Bc1, use_prev = false))]
+    fn py_from_encoded(
+        encoded: (Vec<DrawCall>, Vec<u8>),
+        frame: &ProcessedFrame,
+        format: BcFormat,
+        use_prev: bool,
+    ) -> PyResult<Self> {
+        Ok(Self::from_encoded(encoded, frame, format, use_prev)?)
+    }
+
+    #[getter(width)]
+    fn py_width(&self) -> u32 {
+        self.width()
+    }
+
+    #[getter(height)]
+    fn py_height(&self) -> u32 {
+        self.height()
+    }
+
+    #[getter(format)]
+    fn py_format(&self) -> BcFormat {
+        self.format()
+    }
+
+    #[getter(uses_prev)]
+    fn py_uses_prev(&self) -> bool {
+        self.uses_prev()
+    }
+
+    #[pyo3(name = "decode", signature = (prev_info = None))]
+    fn py_decode(&self, prev_info: Option<(&[u8], u32, u32, i32, i32)>) -> PyResult<(Vec<u8>, Vec<u8>)> {
+        let (image, blocks) = self.decode(prev_info)?;
+        Ok((to_png(image)?, blocks))
+    }
+}
+
+#[pymethods]
+impl SldFrame {
+    #[new]
+    #[pyo3(signature = (
+        canvas_width, canvas_height, canvas_hotspot_x, canvas_hotspot_y, frame_index,
+        main_layer = None, shadow_layer = None, unknown_layer = None, damage_mask_layer = None,
+        player_color_layer = None, unknown1 = 0
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_new(
+        canvas_width: u16,
+        canvas_height: u16,
+        canvas_hotspot_x: u16,
+        canvas_hotspot_y: u16,
+        frame_index: u16,
+        main_layer: Option<SldLayer>,
+        shadow_layer: Option<SldLayer>,
+        unknown_layer: Option<SldLayer>,
+        damage_mask_layer: Option<SldLayer>,
+        player_color_layer: Option<SldLayer>,
+        unknown1: u8,
+    ) -> Self {
+        Self::new(
+            canvas_width, canvas_height, canvas_hotspot_x, canvas_hotspot_y, frame_index,
+            main_layer, shadow_layer, unknown_layer, damage_mask_layer, player_color_layer, unknown1,
+        )
+    }
+
+    #[pyo3(name = "layers")]
+    fn py_layers(&self) -> Vec<(u8, SldLayer)> {
+        self.layers().into_iter()
+            .map(|(kind, layer)| (kind, layer.clone()))
+            .collect()
+    }
+}
+
+#[pymethods]
+impl SldFile {
+    #[new]
+    #[pyo3(signature = (frames, version = SLD_VERSION))]
+    fn py_new(frames: Vec<SldFrame>, version: u16) -> PyResult<Self> {
+        Ok(Self::new(frames, version)?)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_bytes")]
+    fn py_from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        Ok(Self::from_bytes(bytes)?)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_file")]
+    fn py_from_file(path: PathBuf) -> PyResult<Self> {
+        Ok(Self::from_file(path)?)
+    }
+
+    #[pyo3(name = "to_bytes")]
+    fn py_to_bytes(&self) -> PyResult<Vec<u8>> {
+        Ok(self.to_bytes()?)
+    }
+
+    #[pyo3(name = "to_file")]
+    fn py_to_file(&self, path: PathBuf) -> PyResult<()> {
+        Ok(self.to_file(path)?)
+    }
+}
@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+use pyo3::prelude::*;
+use crate::python::to_png;
+use crate::slp::{DecodedSlpFrame, SlpFile, SlpFrame};
+
+#[pymethods]
+impl SlpFrame {
+    #[getter]
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[getter]
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[getter]
+    fn hotspot_x(&self) -> i32 {
+        self.hotspot_x
+    }
+
+    #[getter]
+    fn hotspot_y(&self) -> i32 {
+        self.hotspot_y
+    }
+
+    #[getter]
+    fn palette_offset(&self) -> u32 {
+        self.palette_offset
+    }
+
+    #[getter]
+    fn properties(&self) -> u32 {
+        self.properties
+    }
+
+    #[pyo3(name = "decode", signature = (palette, player = 1))]
+    fn py_decode(&self, palette: Vec<[u8; 3]>, player: u8) -> PyResult<DecodedSlpFrame> {
+        Ok(self.decode(&palette, player)?)
+    }
+}
+
+#[pymethods]
+impl DecodedSlpFrame {
+    #[getter]
+    fn image_bytes(&self) -> PyResult<Vec<u8>> {
+        to_png(self.image.clone())
+    }
+
+    #[getter]
+    fn image_raw(&self) -> Vec<u8> {
+        self.image.as_raw().clone()
+    }
+
+    #[getter]
+    fn shadow_bytes(&self) -> PyResult<Vec<u8>> {
+        to_png(self.shadow.clone())
+    }
+
+    #[getter]
+    fn shadow_raw(&self) -> Vec<u8> {
+        self.shadow.as_raw().clone()
+    }
+
+    #[getter]
+    fn player_color_bytes(&self) -> PyResult<Vec<u8>> {
+        to_png(self.player_color.clone())
+    }
+
+    #[getter]
+    fn player_color_raw(&self) -> Vec<u8> {
+        self.player_color.as_raw().clone()
+    }
+
+    #[getter]
+    fn outline_bytes(&self) -> PyResult<Vec<u8>> {
+        to_png(self.outline.clone())
+    }
+
+    #[getter]
+    fn outline_raw(&self) -> Vec<u8> {
+        self.outline.as_raw().clone()
+    }
+
+    #[getter]
+    fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    #[getter]
+    fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    #[getter]
+    fn hotspot(&self) -> (i32, i32) {
+        self.hotspot
+    }
+}
+
+#[pymethods]
+impl SlpFile {
+    #[staticmethod]
+    #[pyo3(name = "from_bytes")]
+    fn py_from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        Ok(Self::from_bytes(bytes)?)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_file")]
+    fn py_from_file(path: PathBuf) -> PyResult<Self> {
+        Ok(Self::from_file(path)?)
+    }
+
+    #[pyo3(name = "decode", signature = (palette, player = 1))]
+    fn py_decode(&self, py: Python<'_>, palette: Vec<[u8; 3]>, player: u8) -> PyResult<Vec<DecodedSlpFrame>> {
+        Ok(py.detach(|| self.decode(&palette, player))?)
+    }
+}
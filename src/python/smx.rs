@@ -0,0 +1,128 @@
+use std::path::PathBuf;
+use pyo3::prelude::*;
+use crate::python::to_png;
+use crate::smx::{DecodedSmxFrame, SmxFile, SmxFrame, SmxLayer};
+
+#[pymethods]
+impl SmxLayer {
+    #[getter]
+    fn width(&self) -> u16 {
+        self.width
+    }
+
+    #[getter]
+    fn height(&self) -> u16 {
+        self.height
+    }
+
+    #[getter]
+    fn hotspot_x(&self) -> i16 {
+        self.hotspot_x
+    }
+
+    #[getter]
+    fn hotspot_y(&self) -> i16 {
+        self.hotspot_y
+    }
+
+    #[getter]
+    fn unknown(&self) -> u32 {
+        self.unknown
+    }
+}
+
+#[pymethods]
+impl SmxFrame {
+    #[pyo3(name = "decode", signature = (palette, player_palette = None))]
+    fn py_decode(&self, palette: Vec<[u8; 3]>, player_palette: Option<Vec<[u8; 3]>>) -> PyResult<DecodedSmxFrame> {
+        Ok(self.decode(&palette, player_palette.as_deref())?)
+    }
+}
+
+#[pymethods]
+impl DecodedSmxFrame {
+    #[getter]
+    fn image_bytes(&self) -> PyResult<Vec<u8>> {
+        to_png(self.image.clone())
+    }
+
+    #[getter]
+    fn image_raw(&self) -> Vec<u8> {
+        self.image.as_raw().clone()
+    }
+
+    #[getter]
+    fn player_color_bytes(&self) -> PyResult<Vec<u8>> {
+        to_png(self.player_color.clone())
+    }
+
+    #[getter]
+    fn player_color_raw(&self) -> Vec<u8> {
+        self.player_color.as_raw().clone()
+    }
+
+    #[getter]
+    fn shadow_bytes(&self) -> PyResult<Vec<u8>> {
+        to_png(self.shadow.clone())
+    }
+
+    #[getter]
+    fn shadow_raw(&self) -> Vec<u8> {
+        self.shadow.as_raw().clone()
+    }
+
+    #[getter]
+    fn outline_bytes(&self) -> PyResult<Vec<u8>> {
+        to_png(self.outline.clone())
+    }
+
+    #[getter]
+    fn outline_raw(&self) -> Vec<u8> {
+        self.outline.as_raw().clone()
+    }
+
+    #[getter]
+    fn hotspot(&self) -> (i32, i32) {
+        self.hotspot
+    }
+
+    #[getter]
+    fn shadow_hotspot(&self) -> (i32, i32) {
+        self.shadow_hotspot
+    }
+
+    #[getter]
+    fn outline_hotspot(&self) -> (i32, i32) {
+        self.outline_hotspot
+    }
+
+    #[getter(shadow_offset)]
+    fn py_shadow_offset(&self) -> (i32, i32) {
+        self.shadow_offset()
+    }
+}
+
+#[pymethods]
+impl SmxFile {
+    #[staticmethod]
+    #[pyo3(name = "from_bytes")]
+    fn py_from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        Ok(Self::from_bytes(bytes)?)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_file")]
+    fn py_from_file(path: PathBuf) -> PyResult<Self> {
+        Ok(Self::from_file(path)?)
+    }
+
+    #[pyo3(name = "decode", signature = (palette, player_palette = None))]
+    fn py_decode(
+        &self,
+        py: Python<'_>,
+        palette: Vec<[u8; 3]>,
+        player_palette: Option<Vec<[u8; 3]>>,
+    ) -> PyResult<Vec<DecodedSmxFrame>> {
+        Ok(py.detach(|| self.decode(&palette, player_palette.as_deref()))?)
+    }
+}
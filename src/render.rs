@@ -1,42 +1,92 @@
-use image::{ImageBuffer, Rgba};
-use pyo3::prelude::*;
-use pyo3::exceptions::PyValueError;
-
-#[pyfunction]
-#[pyo3(name = "render", signature = (
-    main_layer, shadow_layer, shadow_layer_offset, player_color_mask, damage_mask = None, color = (255, 0, 0)
-))]
+use image::{GrayImage, ImageBuffer, Rgba, RgbaImage};
+use crate::error::{Error, Result};
+use crate::palette::Palette;
+
+/// The damage percentages at which the red, green and blue channels of the damage mask take effect, the 25%, 50% and
+/// 75% damage states in which buildings are previewed. How the game maps the mask's channels to these states and how
+/// it blends them is not publicly documented, so this mapping and the plain darkening in `damage_amount` are
+/// assumptions, which in-game screenshots should confirm
+const DAMAGE_STAGES: [u32; 3] = [25, 50, 75];
+
+/// How the pixels under the player color mask are colored
+#[derive(Clone, Debug)]
+pub enum PlayerColor {
+    /// The luminance of the main layer is multiplied with the color
+    Rgb(u8, u8, u8),
+    /// The color at the index of the palette, e.g. a player's color in the game's palette, used like `Rgb`
+    Indexed(Palette, usize),
+    /// A player color palette ordered from dark to light, in which the luminance of the main layer picks the color
+    Palette(Palette),
+}
+
+impl From<(u8, u8, u8)> for PlayerColor {
+    fn from((r, g, b): (u8, u8, u8)) -> Self {
+        PlayerColor::Rgb(r, g, b)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    /// The player color
+    pub color: PlayerColor,
+    /// The color of the shadow, which is drawn underneath the unit
+    pub shadow_color: (u8, u8, u8),
+    /// Scales the shadow layer's opacity, between 0 and 1
+    pub shadow_opacity: f32,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            color: PlayerColor::Rgb(255, 0, 0),
+            shadow_color: (0, 0, 0),
+            shadow_opacity: 1.0,
+        }
+    }
+}
+
+/// Renders an SLD frame given the different layers after decoding. The damage mask is aligned with the main layer and
+/// comes with the damage percentage of the building
 pub fn render_frames(
-    main_layer: &[u8],
-    shadow_layer: &[u8],
+    main_layer: &RgbaImage,
+    shadow_layer: &GrayImage,
     shadow_layer_offset: (i32, i32),
-    player_color_mask: &[u8],
-    damage_mask: Option<(&[u8], u32)>,
-    color: (u8, u8, u8),
-) -> PyResult<Vec<u8>> {
-    let main_layer = image::load_from_memory(main_layer)
-        .map_err(|e| PyValueError::new_err(format!("Failed to load shading image: {}", e)))?
-        .to_rgba8();
-    
-    let shadow_layer = image::load_from_memory(shadow_layer)
-        .map_err(|e| PyValueError::new_err(format!("Failed to load shadow image: {}", e)))?
-        .to_luma8();
-    
-    let player_color_mask = image::load_from_memory(player_color_mask)
-        .map_err(|e| PyValueError::new_err(format!("Failed to load mask image: {}", e)))?
-        .to_luma8();
-
-    let damage_mask = damage_mask.map(|(damage_mask, percent)| {
-        image::load_from_memory(damage_mask)
-            .map_err(|e| PyValueError::new_err(format!("Failed to load damage_mask image: {}", e)))
-            .map(|img| (img.to_rgba8(), percent))
-    }).transpose()?;
-    
-    let color_linear = [
-        srgb_to_linear(color.0),
-        srgb_to_linear(color.1),
-        srgb_to_linear(color.2),
+    player_color_mask: &GrayImage,
+    damage_mask: Option<(&RgbaImage, u32)>,
+    options: &RenderOptions,
+) -> Result<RgbaImage> {
+    if player_color_mask.dimensions() != main_layer.dimensions() {
+        return Err(Error::InvalidInput(format!(
+            "Player color mask dimensions {:?} do not match the main layer dimensions {:?}",
+            player_color_mask.dimensions(), main_layer.dimensions()
+        )));
+    }
+
+    if let Some((damage_mask, _)) = damage_mask && damage_mask.dimensions() != main_layer.dimensions() {
+        return Err(Error::InvalidInput(format!(
+            "Damage mask dimensions {:?} do not match the main layer dimensions {:?}",
+            damage_mask.dimensions(), main_layer.dimensions()
+        )));
+    }
+
+    let RenderOptions { ref color, shadow_color, shadow_opacity } = *options;
+
+    let to_linear = |(r, g, b): (u8, u8, u8)| [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b)];
+    let shades = match color {
+        PlayerColor::Rgb(r, g, b) => vec![to_linear((*r, *g, *b))],
+        PlayerColor::Indexed(palette, index) => vec![to_linear(palette.color(*index)?)],
+        PlayerColor::Palette(palette) if palette.colors.is_empty() => {
+            return Err(Error::InvalidInput("The player color palette has no colors".to_string()));
+        },
+        PlayerColor::Palette(palette) => palette.colors.iter().map(|&[r, g, b]| to_linear((r, g, b))).collect(),
+    };
+
+    let shadow_linear = [
+        srgb_to_linear(shadow_color.0),
+        srgb_to_linear(shadow_color.1),
+        srgb_to_linear(shadow_color.2),
     ];
+    let shadow_opacity = shadow_opacity.clamp(0.0, 1.0);
 
     let (main_width, main_height) = main_layer.dimensions();
     let (shadow_width, shadow_height) = shadow_layer.dimensions();
@@ -52,10 +102,13 @@ pub fn render_frames(
             let (mx, my) = offset((x, y), main_off);
             let (sx, sy) = offset((x, y), shadow_off);
             
-            let (main_pixel, mask) = if mx < 0 || my < 0 || mx >= main_width as i32 || my >= main_height as i32 {
-                (&Rgba([0, 0, 0, 0]), 0)
+            let (main_pixel, mask, damage) = if mx < 0 || my < 0 || mx >= main_width as i32 || my >= main_height as i32 {
+                (&Rgba([0, 0, 0, 0]), 0, 0.0)
             } else {
-                (main_layer.get_pixel(mx as u32, my as u32), player_color_mask.get_pixel(mx as u32, my as u32)[0])
+                let damage = damage_mask.map_or(0.0, |(damage_mask, percent)| {
+                    damage_amount(damage_mask.get_pixel(mx as u32, my as u32), percent)
+                });
+                (main_layer.get_pixel(mx as u32, my as u32), player_color_mask.get_pixel(mx as u32, my as u32)[0], damage)
             };
             let shadow = if sx < 0 || sy < 0 || sx >= shadow_width as i32 || sy >= shadow_height as i32 {
                 0
@@ -75,11 +128,14 @@ pub fn render_frames(
                 0.0722 * main_linear[2]
             ) * 5.0;
             
-            let tinted = [
-                luminance * color_linear[0],
-                luminance * color_linear[1],
-                luminance * color_linear[2],
-            ];
+            let tinted = match color {
+                PlayerColor::Palette(_) => shades[(luminance.min(1.0) * (shades.len() - 1) as f32).round() as usize],
+                _ => [
+                    luminance * shades[0][0],
+                    luminance * shades[0][1],
+                    luminance * shades[0][2],
+                ],
+            };
             
             let mask = mask as f32 / 255.0;
             let blended = [
@@ -88,22 +144,52 @@ pub fn render_frames(
                 mask * tinted[2] + (1.0 - mask) * main_linear[2],
             ];
             
+            let damaged = [
+                blended[0] * (1.0 - damage),
+                blended[1] * (1.0 - damage),
+                blended[2] * (1.0 - damage),
+            ];
+
+            // the unit is drawn over its shadow
+            let main_alpha = main_pixel[3] as f32 / 255.0;
+            let shadow_alpha = shadow as f32 / 255.0 * shadow_opacity * (1.0 - main_alpha);
+            let alpha = main_alpha + shadow_alpha;
+
+            let composited = if alpha == 0.0 {
+                [0.0; 3]
+            } else {
+                [
+                    (damaged[0] * main_alpha + shadow_linear[0] * shadow_alpha) / alpha,
+                    (damaged[1] * main_alpha + shadow_linear[1] * shadow_alpha) / alpha,
+                    (damaged[2] * main_alpha + shadow_linear[2] * shadow_alpha) / alpha,
+                ]
+            };
+
             let final_rgb = [
-                linear_to_srgb(blended[0]),
-                linear_to_srgb(blended[1]),
-                linear_to_srgb(blended[2]),
-                if shadow == 0 { main_pixel[3] } else { shadow }
+                linear_to_srgb(composited[0]),
+                linear_to_srgb(composited[1]),
+                linear_to_srgb(composited[2]),
+                (alpha * 255.0).round() as u8,
             ];
             
             output.put_pixel(x, y, Rgba([final_rgb[0], final_rgb[1], final_rgb[2], final_rgb[3]]));
         }
     }
 
-    let mut png_bytes = Vec::new();
-    output.write_to(&mut std::io::Cursor::new(&mut png_bytes), image::ImageFormat::Png)
-        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(output)
+}
 
-    Ok(png_bytes)
+/// The damage mask's red, green and blue channels hold how much a pixel is darkened once the building has taken
+/// 25%, 50% and 75% damage respectively, see `DAMAGE_STAGES`. Every stage that has been reached applies, and the
+/// strongest one wins
+#[inline]
+fn damage_amount(mask: &Rgba<u8>, percent: u32) -> f32 {
+    DAMAGE_STAGES.iter()
+        .zip(mask.0)
+        .filter(|&(&stage, _)| percent >= stage)
+        .map(|(_, value)| value)
+        .max()
+        .unwrap_or(0) as f32 / 255.0
 }
 
 #[inline]
@@ -144,4 +230,69 @@ fn linear_to_srgb(c: f32) -> u8 {
         1.055 * c.powf(1.0 / 2.4) - 0.055
     };
     (c.clamp(0.0, 1.0) * 255.0).round() as u8
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders a frame without player color
+    fn render(
+        main_layer: &RgbaImage,
+        shadow_layer: &GrayImage,
+        shadow_layer_offset: (i32, i32),
+        damage_mask: Option<(&RgbaImage, u32)>,
+        options: &RenderOptions,
+    ) -> RgbaImage {
+        let player_color_mask = GrayImage::new(main_layer.width(), main_layer.height());
+        render_frames(main_layer, shadow_layer, shadow_layer_offset, &player_color_mask, damage_mask, options).unwrap()
+    }
+
+    #[test]
+    fn damage_stages_apply_from_their_percentage() {
+        let mask = Rgba([64, 128, 255, 0]);
+        let amounts = [0, 24, 25, 50, 74, 75, 100].map(|percent| damage_amount(&mask, percent));
+        assert_eq!(amounts, [0, 0, 64, 128, 128, 255, 255].map(|value| value as f32 / 255.0));
+        // a stronger earlier stage is not undone by a weaker later one
+        assert_eq!(damage_amount(&Rgba([200, 100, 0, 0]), 50), 200.0 / 255.0);
+    }
+
+    #[test]
+    fn damage_darkens_the_main_layer() {
+        let main = RgbaImage::from_pixel(2, 1, Rgba([255, 255, 255, 255]));
+        let mut mask = RgbaImage::new(2, 1);
+        mask.put_pixel(0, 0, Rgba([255, 0, 0, 0]));
+        let shadow = GrayImage::new(2, 1);
+
+        let intact = render(&main, &shadow, (0, 0), Some((&mask, 20)), &RenderOptions::default());
+        assert_eq!(intact, main);
+        let damaged = render(&main, &shadow, (0, 0), Some((&mask, 25)), &RenderOptions::default());
+        assert_eq!(damaged.get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+        assert_eq!(damaged.get_pixel(1, 0), &Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn shadow_is_drawn_underneath_the_unit() {
+        // an opaque and a transparent main layer pixel, both over a full shadow
+        let mut main = RgbaImage::new(2, 1);
+        main.put_pixel(0, 0, Rgba([255, 255, 255, 255]));
+        let shadow = GrayImage::from_pixel(2, 1, image::Luma([255]));
+        let options = RenderOptions { shadow_color: (0, 0, 255), shadow_opacity: 0.5, ..Default::default() };
+
+        let rendered = render(&main, &shadow, (0, 0), None, &options);
+        assert_eq!(rendered.get_pixel(0, 0), &Rgba([255, 255, 255, 255]));
+        assert_eq!(rendered.get_pixel(1, 0), &Rgba([0, 0, 255, 128]));
+    }
+
+    #[test]
+    fn canvas_covers_the_offset_shadow() {
+        let main = RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, 255]));
+        let shadow = GrayImage::from_pixel(1, 1, image::Luma([255]));
+
+        let rendered = render(&main, &shadow, (1, 1), None, &RenderOptions::default());
+        assert_eq!(rendered.dimensions(), (2, 2));
+        assert_eq!(rendered.get_pixel(0, 0), &Rgba([255, 255, 255, 255]));
+        assert_eq!(rendered.get_pixel(1, 0), &Rgba([0, 0, 0, 0]));
+        assert_eq!(rendered.get_pixel(1, 1), &Rgba([0, 0, 0, 255]));
+    }
+}
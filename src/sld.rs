@@ -0,0 +1,555 @@
+use std::path::PathBuf;
+use image::RgbaImage;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use crate::encode_decode::decode;
+use crate::error::{Error, Result};
+use crate::format::{BcFormat, DrawCall, PrevInfo};
+use crate::preprocess::ProcessedFrame;
+
+pub const LAYER_MAIN: u8 = 0x01;
+pub const LAYER_SHADOW: u8 = 0x02;
+pub const LAYER_UNKNOWN: u8 = 0x04;
+pub const LAYER_DAMAGE_MASK: u8 = 0x08;
+pub const LAYER_PLAYER_COLOR: u8 = 0x10;
+
+/// Set in a layer's flag when skipped blocks are copied from the previous frame's layer
+pub const FLAG_USE_PREV: u8 = 0x80;
+/// Set in a layer's flag when its blocks are BC4 compressed instead of BC1
+pub const FLAG_BC4: u8 = 0x01;
+
+const SLD_SIGNATURE: &[u8; 4] = b"SLDX";
+pub const SLD_VERSION: u16 = 4;
+const BLOCK_SIZE: usize = 8;
+
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct SldHeader {
+    pub version: u16,
+    pub num_frames: u16,
+    pub unknown1: u16,
+    pub unknown2: u16,
+    pub unknown3: u32,
+}
+
+/// For the damage mask and player color layers, only `flag1` and `unknown1` are stored in the file, and the
+/// offsets are those of the main layer of the same frame
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct SldLayerHeader {
+    pub offset_x1: u16,
+    pub offset_y1: u16,
+    pub offset_x2: u16,
+    pub offset_y2: u16,
+    pub flag1: u8,
+    pub unknown1: u8,
+}
+
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct SldLayer {
+    pub header: SldLayerHeader,
+    pub commands: Vec<DrawCall>,
+    pub blocks: Vec<u8>,
+    /// Bytes after the blocks that are counted in the layer's content length but not used by its commands. They are
+    /// kept so that a file is written back unchanged
+    pub trailing: Vec<u8>,
+}
+
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct SldFrame {
+    pub canvas_width: u16,
+    pub canvas_height: u16,
+    pub canvas_hotspot_x: u16,
+    pub canvas_hotspot_y: u16,
+    pub frame_type: u8,
+    pub unknown1: u8,
+    pub frame_index: u16,
+
+    pub main_layer: Option<SldLayer>,
+    pub shadow_layer: Option<SldLayer>,
+    pub unknown_layer: Option<SldLayer>,
+    pub damage_mask_layer: Option<SldLayer>,
+    pub player_color_layer: Option<SldLayer>,
+}
+
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct SldFile {
+    pub header: SldHeader,
+    pub frames: Vec<SldFrame>,
+}
+
+impl SldLayerHeader {
+    pub fn new(offset_x1: u16, offset_y1: u16, offset_x2: u16, offset_y2: u16, flag1: u8, unknown1: u8) -> Self {
+        Self { offset_x1, offset_y1, offset_x2, offset_y2, flag1, unknown1 }
+    }
+}
+
+impl SldLayer {
+    pub fn new(header: SldLayerHeader, commands: Vec<DrawCall>, blocks: Vec<u8>) -> Self {
+        Self { header, commands, blocks, trailing: Vec::new() }
+    }
+
+    /// Creates a layer from the commands and blocks generated by `encode`, positioned at the offsets of the given
+    /// frame. `use_prev` must be set if `prev_info` was passed to `encode` for this frame
+    pub fn from_encoded(
+        encoded: (Vec<DrawCall>, Vec<u8>),
+        frame: &ProcessedFrame,
+        format: BcFormat,
+        use_prev: bool,
+    ) -> Result<Self> {
+        let mut flag1 = match format {
+            BcFormat::Bc1 => 0,
+            BcFormat::Bc4 => FLAG_BC4,
+            BcFormat::Bc7 => return Err(Error::InvalidInput("SLD layers cannot be BC7 compressed".to_string())),
+        };
+        if use_prev {
+            flag1 |= FLAG_USE_PREV;
+        }
+
+        let offset = |value: u32| u16::try_from(value)
+            .map_err(|_| Error::InvalidInput(format!("Offset {value} does not fit in an SLD layer header")));
+
+        let header = SldLayerHeader::new(
+            offset(frame.offset_x1)?,
+            offset(frame.offset_y1)?,
+            offset(frame.offset_x2)?,
+            offset(frame.offset_y2)?,
+            flag1,
+            0,
+        );
+        let (commands, blocks) = encoded;
+
+        Ok(Self::new(header, commands, blocks))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.header.offset_x2.saturating_sub(self.header.offset_x1) as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        self.header.offset_y2.saturating_sub(self.header.offset_y1) as u32
+    }
+
+    pub fn format(&self) -> BcFormat {
+        if self.header.flag1 & FLAG_BC4 != 0 {
+            BcFormat::Bc4
+        } else {
+            BcFormat::Bc1
+        }
+    }
+
+    pub fn uses_prev(&self) -> bool {
+        self.header.flag1 & FLAG_USE_PREV != 0
+    }
+
+    /// Decodes this layer, see `decode` for the meaning of `prev_info` and the return value
+    pub fn decode(&self, prev_info: Option<PrevInfo>) -> Result<(RgbaImage, Vec<u8>)> {
+        decode(
+            self.blocks.clone(),
+            self.width(),
+            self.height(),
+            self.format(),
+            Some(&self.commands),
+            prev_info,
+        )
+    }
+}
+
+impl SldFrame {
+    /// The frame type is derived from the layers that are present
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        canvas_width: u16,
+        canvas_height: u16,
+        canvas_hotspot_x: u16,
+        canvas_hotspot_y: u16,
+        frame_index: u16,
+        main_layer: Option<SldLayer>,
+        shadow_layer: Option<SldLayer>,
+        unknown_layer: Option<SldLayer>,
+        damage_mask_layer: Option<SldLayer>,
+        player_color_layer: Option<SldLayer>,
+        unknown1: u8,
+    ) -> Self {
+        let mut frame = Self {
+            canvas_width,
+            canvas_height,
+            canvas_hotspot_x,
+            canvas_hotspot_y,
+            frame_type: 0,
+            unknown1,
+            frame_index,
+            main_layer,
+            shadow_layer,
+            unknown_layer,
+            damage_mask_layer,
+            player_color_layer,
+        };
+        frame.frame_type = frame.layers().iter().fold(0, |frame_type, (kind, _)| frame_type | kind);
+        frame
+    }
+
+    /// The layers present in this frame in the order they are stored, paired with their layer type bit
+    pub fn layers(&self) -> Vec<(u8, &SldLayer)> {
+        [
+            (LAYER_MAIN, &self.main_layer),
+            (LAYER_SHADOW, &self.shadow_layer),
+            (LAYER_UNKNOWN, &self.unknown_layer),
+            (LAYER_DAMAGE_MASK, &self.damage_mask_layer),
+            (LAYER_PLAYER_COLOR, &self.player_color_layer),
+        ].into_iter()
+            .filter_map(|(kind, layer)| layer.as_ref().map(|layer| (kind, layer)))
+            .collect()
+    }
+}
+
+impl SldFile {
+    pub fn new(frames: Vec<SldFrame>, version: u16) -> Result<Self> {
+        let num_frames = u16::try_from(frames.len())
+            .map_err(|_| Error::InvalidInput(format!("Too many frames ({}) for an SLD file", frames.len())))?;
+
+        Ok(Self {
+            header: SldHeader {
+                version,
+                num_frames,
+                unknown1: 0,
+                unknown2: 0x10,
+                unknown3: 0xFF000000,
+            },
+            frames,
+        })
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = SldReader::new(bytes);
+
+        let signature = reader.bytes(4)?;
+        if signature != SLD_SIGNATURE {
+            return Err(Error::Parse(format!(
+                "Invalid SLD signature '{}'", String::from_utf8_lossy(signature)
+            )));
+        }
+
+        let header = SldHeader {
+            version: reader.u16()?,
+            num_frames: reader.u16()?,
+            unknown1: reader.u16()?,
+            unknown2: reader.u16()?,
+            unknown3: reader.u32()?,
+        };
+
+        let mut frames = Vec::with_capacity(header.num_frames as usize);
+        for _ in 0..header.num_frames {
+            frames.push(reader.frame()?);
+        }
+
+        Ok(Self { header, frames })
+    }
+
+    pub fn from_file(path: PathBuf) -> Result<Self> {
+        let bytes = std::fs::read(&path).map_err(|e| Error::Io(path.clone(), e))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Writing a parsed file back reproduces its bytes, except that the padding after each layer is written as zeros
+    /// even if the file had other bytes there or ended without padding
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.header.num_frames as usize != self.frames.len() {
+            return Err(Error::InvalidInput(format!(
+                "Header specifies {} frames but the file has {}", self.header.num_frames, self.frames.len()
+            )));
+        }
+
+        let mut out = Vec::new();
+        out.extend_from_slice(SLD_SIGNATURE);
+        out.extend_from_slice(&self.header.version.to_le_bytes());
+        out.extend_from_slice(&self.header.num_frames.to_le_bytes());
+        out.extend_from_slice(&self.header.unknown1.to_le_bytes());
+        out.extend_from_slice(&self.header.unknown2.to_le_bytes());
+        out.extend_from_slice(&self.header.unknown3.to_le_bytes());
+
+        for frame in &self.frames {
+            write_frame(&mut out, frame)?;
+        }
+
+        Ok(out)
+    }
+
+    pub fn to_file(&self, path: PathBuf) -> Result<()> {
+        std::fs::write(&path, self.to_bytes()?).map_err(|e| Error::Io(path.clone(), e))
+    }
+}
+
+fn write_frame(out: &mut Vec<u8>, frame: &SldFrame) -> Result<()> {
+    out.extend_from_slice(&frame.canvas_width.to_le_bytes());
+    out.extend_from_slice(&frame.canvas_height.to_le_bytes());
+    out.extend_from_slice(&frame.canvas_hotspot_x.to_le_bytes());
+    out.extend_from_slice(&frame.canvas_hotspot_y.to_le_bytes());
+    out.push(frame.frame_type);
+    out.push(frame.unknown1);
+    out.extend_from_slice(&frame.frame_index.to_le_bytes());
+
+    for (kind, layer) in frame.layers() {
+        if frame.frame_type & kind == 0 {
+            return Err(Error::InvalidInput(format!(
+                "Frame {} has a layer of type 0x{kind:02X} which is not set in its frame type 0x{:02X}",
+                frame.frame_index, frame.frame_type
+            )));
+        }
+        write_layer(out, kind, layer)?;
+    }
+
+    Ok(())
+}
+
+fn write_layer(out: &mut Vec<u8>, kind: u8, layer: &SldLayer) -> Result<()> {
+    let num_blocks: usize = layer.commands.iter().map(|cmd| cmd.draw as usize).sum();
+    if num_blocks * BLOCK_SIZE != layer.blocks.len() {
+        return Err(Error::InvalidInput(format!(
+            "Draw commands specify {num_blocks} blocks but the layer has {} bytes of block data",
+            layer.blocks.len()
+        )));
+    }
+    let header = &layer.header;
+    if has_offsets(kind) && (header.offset_x2 < header.offset_x1 || header.offset_y2 < header.offset_y1) {
+        return Err(Error::InvalidInput(format!(
+            "Layer of type 0x{kind:02X} ends at ({}, {}) before it starts at ({}, {})",
+            header.offset_x2, header.offset_y2, header.offset_x1, header.offset_y1
+        )));
+    }
+    let num_commands = u16::try_from(layer.commands.len())
+        .map_err(|_| Error::InvalidInput(format!("Too many draw commands ({}) for an SLD layer", layer.commands.len())))?;
+
+    let header_size = if has_offsets(kind) { 10 } else { 2 };
+    let content_length =
+        4 + header_size + 2 + layer.commands.len() * 2 + layer.blocks.len() + layer.trailing.len();
+
+    out.extend_from_slice(&(content_length as u32).to_le_bytes());
+    if has_offsets(kind) {
+        out.extend_from_slice(&layer.header.offset_x1.to_le_bytes());
+        out.extend_from_slice(&layer.header.offset_y1.to_le_bytes());
+        out.extend_from_slice(&layer.header.offset_x2.to_le_bytes());
+        out.extend_from_slice(&layer.header.offset_y2.to_le_bytes());
+    }
+    out.push(layer.header.flag1);
+    out.push(layer.header.unknown1);
+
+    out.extend_from_slice(&num_commands.to_le_bytes());
+    for cmd in &layer.commands {
+        out.push(cmd.skip);
+        out.push(cmd.draw);
+    }
+    out.extend_from_slice(&layer.blocks);
+    out.extend_from_slice(&layer.trailing);
+    out.resize(out.len() + padding(content_length), 0);
+
+    Ok(())
+}
+
+struct SldReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SldReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.pos + len > self.bytes.len() {
+            return Err(Error::Parse(format!(
+                "Unexpected end of SLD data: tried to read {len} bytes at offset {} but the file is {} bytes",
+                self.pos, self.bytes.len()
+            )));
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn frame(&mut self) -> Result<SldFrame> {
+        let mut frame = SldFrame {
+            canvas_width: self.u16()?,
+            canvas_height: self.u16()?,
+            canvas_hotspot_x: self.u16()?,
+            canvas_hotspot_y: self.u16()?,
+            frame_type: self.u8()?,
+            unknown1: self.u8()?,
+            frame_index: self.u16()?,
+            main_layer: None,
+            shadow_layer: None,
+            unknown_layer: None,
+            damage_mask_layer: None,
+            player_color_layer: None,
+        };
+
+        for kind in [LAYER_MAIN, LAYER_SHADOW, LAYER_UNKNOWN, LAYER_DAMAGE_MASK, LAYER_PLAYER_COLOR] {
+            if frame.frame_type & kind == 0 {
+                continue;
+            }
+            let main_header = frame.main_layer.as_ref().map(|layer| &layer.header);
+            let layer = self.layer(kind, main_header)?;
+            match kind {
+                LAYER_MAIN => frame.main_layer = Some(layer),
+                LAYER_SHADOW => frame.shadow_layer = Some(layer),
+                LAYER_UNKNOWN => frame.unknown_layer = Some(layer),
+                LAYER_DAMAGE_MASK => frame.damage_mask_layer = Some(layer),
+                _ => frame.player_color_layer = Some(layer),
+            }
+        }
+
+        Ok(frame)
+    }
+
+    fn layer(&mut self, kind: u8, main_header: Option<&SldLayerHeader>) -> Result<SldLayer> {
+        let start = self.pos;
+        let content_length = self.u32()? as usize;
+
+        let header = if has_offsets(kind) {
+            SldLayerHeader {
+                offset_x1: self.u16()?,
+                offset_y1: self.u16()?,
+                offset_x2: self.u16()?,
+                offset_y2: self.u16()?,
+                flag1: self.u8()?,
+                unknown1: self.u8()?,
+            }
+        } else {
+            let Some(main_header) = main_header else {
+                return Err(Error::Parse(format!(
+                    "Layer of type 0x{kind:02X} requires a main layer in the same frame"
+                )));
+            };
+            SldLayerHeader {
+                flag1: self.u8()?,
+                unknown1: self.u8()?,
+                ..main_header.clone()
+            }
+        };
+
+        if header.offset_x2.checked_sub(header.offset_x1).is_none()
+            || header.offset_y2.checked_sub(header.offset_y1).is_none() {
+            return Err(Error::Parse(format!(
+                "Layer at offset {start} ends at ({}, {}) before it starts at ({}, {})",
+                header.offset_x2, header.offset_y2, header.offset_x1, header.offset_y1
+            )));
+        }
+
+        let num_commands = self.u16()? as usize;
+        let mut commands = Vec::with_capacity(num_commands);
+        let mut num_blocks = 0_usize;
+        for _ in 0..num_commands {
+            let cmd = DrawCall::new(self.u8()?, self.u8()?);
+            num_blocks += cmd.draw as usize;
+            commands.push(cmd);
+        }
+
+        let blocks = self.bytes(num_blocks * BLOCK_SIZE)?.to_vec();
+
+        let end = start + content_length;
+        if end < self.pos {
+            return Err(Error::Parse(format!(
+                "Layer at offset {start} has a content length of {content_length} bytes but its commands and blocks use {} bytes",
+                self.pos - start
+            )));
+        }
+        let trailing = self.bytes(end - self.pos)?.to_vec();
+        self.bytes(padding(end).min(self.bytes.len().saturating_sub(end)))?;
+
+        Ok(SldLayer { header, commands, blocks, trailing })
+    }
+}
+
+/// The damage mask and player color layers reuse the offsets of the main layer
+#[inline]
+pub fn has_offsets(kind: u8) -> bool {
+    matches!(kind, LAYER_MAIN | LAYER_SHADOW | LAYER_UNKNOWN)
+}
+
+/// Layers are padded to a multiple of 4 bytes, this padding is not included in their content length
+#[inline]
+pub fn padding(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(offsets: (u16, u16, u16, u16), flag1: u8, commands: Vec<DrawCall>) -> SldLayer {
+        let num_blocks: usize = commands.iter().map(|cmd| cmd.draw as usize).sum();
+        let blocks = (0..num_blocks * BLOCK_SIZE).map(|i| i as u8).collect();
+        let (x1, y1, x2, y2) = offsets;
+        SldLayer::new(SldLayerHeader::new(x1, y1, x2, y2, flag1, 0), commands, blocks)
+    }
+
+    #[test]
+    fn round_trips_bytes() {
+        // every layer kind, BC4 and use-prev flags, and content lengths that need padding
+        let main = layer((0, 0, 8, 8), 0, vec![DrawCall::new(1, 3)]);
+        let shadow = layer((4, 4, 12, 12), FLAG_BC4, vec![DrawCall::new(0, 2), DrawCall::new(1, 1)]);
+        let mut unknown = layer((0, 0, 4, 4), 0, vec![DrawCall::new(0, 1)]);
+        unknown.trailing = vec![0xAB, 0xCD, 0xEF];
+        let damage_mask = layer((0, 0, 0, 0), 0, vec![DrawCall::new(0, 4)]);
+        let player_color = layer((0, 0, 0, 0), FLAG_BC4, vec![DrawCall::new(2, 2)]);
+        let first = SldFrame::new(
+            16, 16, 8, 8, 0, Some(main), Some(shadow), Some(unknown), Some(damage_mask), Some(player_color), 0,
+        );
+
+        let main = layer((0, 0, 8, 8), FLAG_USE_PREV, vec![DrawCall::new(3, 1)]);
+        let player_color = layer((0, 0, 0, 0), FLAG_BC4 | FLAG_USE_PREV, vec![DrawCall::new(4, 0)]);
+        let second = SldFrame::new(16, 16, 8, 8, 1, Some(main), None, None, None, Some(player_color), 0);
+
+        let bytes = SldFile::new(vec![first, second], SLD_VERSION).unwrap().to_bytes().unwrap();
+        let sld = SldFile::from_bytes(&bytes).unwrap();
+
+        assert_eq!(sld.to_bytes().unwrap(), bytes);
+        assert_eq!(sld.frames[0].unknown_layer.as_ref().unwrap().trailing, [0xAB, 0xCD, 0xEF]);
+        assert_eq!(sld.frames[0].player_color_layer.as_ref().unwrap().width(), 8);
+        assert!(sld.frames[1].main_layer.as_ref().unwrap().uses_prev());
+        assert_eq!(sld.frames[1].player_color_layer.as_ref().unwrap().format(), BcFormat::Bc4);
+    }
+
+    #[test]
+    fn rejects_layer_ending_before_its_start() {
+        let mut bytes = SLD_SIGNATURE.to_vec();
+        // the header with 1 frame, then a 16x16 canvas with its hotspot at (8, 8) and only a main layer
+        for value in [SLD_VERSION, 1, 0, 0, 0, 0, 16, 16, 8, 8] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend([LAYER_MAIN, 0, 0, 0]);
+        // the content length, offsets from (8, 0) to (4, 4), the flags and no commands
+        bytes.extend(16_u32.to_le_bytes());
+        for value in [8_u16, 0, 4, 4] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend([0; 4]);
+
+        assert!(matches!(SldFile::from_bytes(&bytes), Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn does_not_write_layer_ending_before_its_start() {
+        let main = layer((8, 0, 4, 4), 0, vec![]);
+        let frame = SldFrame::new(16, 16, 8, 8, 0, Some(main), None, None, None, None, 0);
+        let sld = SldFile::new(vec![frame], SLD_VERSION).unwrap();
+
+        assert!(matches!(sld.to_bytes(), Err(Error::InvalidInput(_))));
+    }
+}
@@ -0,0 +1,379 @@
+use std::path::PathBuf;
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use crate::error::{Error, Result};
+
+/// Marks a row of the outline table that has no pixels
+const EMPTY_ROW: u16 = 0x8000;
+/// Frames larger than this in either dimension are rejected as corrupt before their pixels are allocated
+const MAX_FRAME_SIZE: u32 = 4096;
+/// Player color pixels of player `n` use the palette indices `16 * n` to `16 * n + 15`
+const PLAYER_COLOR_STRIDE: usize = 16;
+
+const MASK_SET: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct SlpHeader {
+    pub version: String,
+    pub num_frames: u32,
+    pub comment: String,
+}
+
+/// A pixel of an SLP frame before the palette is applied
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlpPixel {
+    Transparent,
+    /// A palette index
+    Color(u8),
+    /// A palette index relative to the player's colors
+    PlayerColor(u8),
+    Shadow,
+    /// Drawn in the player's color where the unit is hidden behind a building
+    PlayerOutline,
+    /// Drawn in black where the unit is hidden behind a building
+    BlackOutline,
+}
+
+/// The hotspot is the anchor of the frame, i.e. the pixel placed at the unit's position
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Debug)]
+pub struct SlpFrame {
+    pub width: u32,
+    pub height: u32,
+    pub hotspot_x: i32,
+    pub hotspot_y: i32,
+    pub palette_offset: u32,
+    pub properties: u32,
+    /// The pixels in row-major order
+    pub pixels: Vec<SlpPixel>,
+}
+
+/// An SLP frame after the palette is applied. The shadow, player color and outline masks have the size of the image
+/// and are white where the frame has such pixels, matching the red channel convention of the SLD mask layers
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Debug)]
+pub struct DecodedSlpFrame {
+    pub image: RgbaImage,
+    pub shadow: RgbaImage,
+    pub player_color: RgbaImage,
+    pub outline: RgbaImage,
+    /// Can be used as the anchor of the frame in `preprocess_images`
+    pub hotspot: (i32, i32),
+}
+
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct SlpFile {
+    pub header: SlpHeader,
+    pub frames: Vec<SlpFrame>,
+}
+
+impl SlpFrame {
+    /// Applies the palette, player color pixels use the colors of the given player (1 to 8 in the default palettes)
+    pub fn decode(&self, palette: &[[u8; 3]], player: u8) -> Result<DecodedSlpFrame> {
+        let color = |idx: usize| palette.get(idx)
+            .map(|&[r, g, b]| Rgba([r, g, b, 255]))
+            .ok_or_else(|| Error::InvalidInput(format!(
+                "Palette index {idx} is out of range for a palette with {} colors", palette.len()
+            )));
+
+        let mut image = RgbaImage::new(self.width, self.height);
+        let mut shadow = RgbaImage::new(self.width, self.height);
+        let mut player_color = RgbaImage::new(self.width, self.height);
+        let mut outline = RgbaImage::new(self.width, self.height);
+
+        for (idx, pixel) in self.pixels.iter().enumerate() {
+            let (x, y) = (idx as u32 % self.width, idx as u32 / self.width);
+            match *pixel {
+                SlpPixel::Transparent => {},
+                SlpPixel::Color(idx) => image.put_pixel(x, y, color(idx as usize)?),
+                SlpPixel::PlayerColor(idx) => {
+                    image.put_pixel(x, y, color(idx as usize + player as usize * PLAYER_COLOR_STRIDE)?);
+                    player_color.put_pixel(x, y, MASK_SET);
+                },
+                SlpPixel::Shadow => shadow.put_pixel(x, y, MASK_SET),
+                SlpPixel::PlayerOutline | SlpPixel::BlackOutline => outline.put_pixel(x, y, MASK_SET),
+            }
+        }
+
+        Ok(DecodedSlpFrame {
+            image,
+            shadow,
+            player_color,
+            outline,
+            hotspot: (self.hotspot_x, self.hotspot_y),
+        })
+    }
+}
+
+impl SlpFile {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let reader = SlpReader::new(bytes);
+
+        let version = reader.bytes(0, 4)?;
+        if !version.starts_with(b"2.0") {
+            return Err(Error::Parse(format!(
+                "Unsupported SLP version '{}', only version 2.0 is supported", String::from_utf8_lossy(version)
+            )));
+        }
+
+        let header = SlpHeader {
+            version: String::from_utf8_lossy(version).into_owned(),
+            num_frames: reader.u32(4)?,
+            comment: String::from_utf8_lossy(reader.bytes(8, 24)?).trim_end_matches('\0').to_string(),
+        };
+
+        let frames = (0..header.num_frames as usize)
+            .map(|idx| reader.frame(32 + idx * 32))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { header, frames })
+    }
+
+    pub fn from_file(path: PathBuf) -> Result<Self> {
+        let bytes = std::fs::read(&path).map_err(|e| Error::Io(path.clone(), e))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Decodes every frame in parallel, see `SlpFrame::decode`
+    pub fn decode(&self, palette: &[[u8; 3]], player: u8) -> Result<Vec<DecodedSlpFrame>> {
+        self.frames.par_iter()
+            .map(|frame| frame.decode(palette, player))
+            .collect()
+    }
+}
+
+struct SlpReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> SlpReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn bytes(&self, pos: usize, len: usize) -> Result<&'a [u8]> {
+        if pos + len > self.bytes.len() {
+            return Err(Error::Parse(format!(
+                "Unexpected end of SLP data: tried to read {len} bytes at offset {pos} but the file is {} bytes",
+                self.bytes.len()
+            )));
+        }
+        Ok(&self.bytes[pos..pos + len])
+    }
+
+    fn u8(&self, pos: usize) -> Result<u8> {
+        Ok(self.bytes(pos, 1)?[0])
+    }
+
+    fn u16(&self, pos: usize) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.bytes(pos, 2)?.try_into().unwrap()))
+    }
+
+    fn u32(&self, pos: usize) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(pos, 4)?.try_into().unwrap()))
+    }
+
+    fn i32(&self, pos: usize) -> Result<i32> {
+        Ok(i32::from_le_bytes(self.bytes(pos, 4)?.try_into().unwrap()))
+    }
+
+    fn frame(&self, pos: usize) -> Result<SlpFrame> {
+        let cmd_table_offset = self.u32(pos)? as usize;
+        let outline_table_offset = self.u32(pos + 4)? as usize;
+        let palette_offset = self.u32(pos + 8)?;
+        let properties = self.u32(pos + 12)?;
+        let width = self.i32(pos + 16)?;
+        let height = self.i32(pos + 20)?;
+        let hotspot_x = self.i32(pos + 24)?;
+        let hotspot_y = self.i32(pos + 28)?;
+
+        let (Ok(width), Ok(height)) = (u32::try_from(width), u32::try_from(height)) else {
+            return Err(Error::Parse(format!("Frame at offset {pos} has a negative size {width}x{height}")));
+        };
+        if width > MAX_FRAME_SIZE || height > MAX_FRAME_SIZE {
+            return Err(Error::Parse(format!(
+                "Frame at offset {pos} has a size of {width}x{height}, which exceeds the maximum of \
+                {MAX_FRAME_SIZE}x{MAX_FRAME_SIZE}"
+            )));
+        }
+        // every row has an entry in both tables
+        self.bytes(outline_table_offset, height as usize * 4)?;
+        self.bytes(cmd_table_offset, height as usize * 4)?;
+
+        let mut pixels = vec![SlpPixel::Transparent; width as usize * height as usize];
+        for (y, row) in pixels.chunks_exact_mut(width.max(1) as usize).enumerate() {
+            let left = self.u16(outline_table_offset + y * 4)?;
+            let right = self.u16(outline_table_offset + y * 4 + 2)?;
+            if left == EMPTY_ROW || right == EMPTY_ROW {
+                continue;
+            }
+
+            let row_offset = self.u32(cmd_table_offset + y * 4)? as usize;
+            let end = row.len().saturating_sub(right as usize);
+            self.row(row_offset, &mut row[..end], left as usize)
+                .map_err(|e| Error::Parse(format!("Failed to read row {y} of the frame at offset {pos}: {e}")))?;
+        }
+
+        Ok(SlpFrame { width, height, hotspot_x, hotspot_y, palette_offset, properties, pixels })
+    }
+
+    /// Reads the commands of a row starting at `x` until its end of row command
+    fn row(&self, mut pos: usize, row: &mut [SlpPixel], mut x: usize) -> Result<()> {
+        loop {
+            let cmd = self.u8(pos)?;
+            pos += 1;
+
+            let (count, run) = match cmd & 0x0F {
+                0x0F => return Ok(()),
+                0x02 => (self.greater_count(cmd, &mut pos)?, Run::Copy(SlpPixel::Color)),
+                0x03 => (self.greater_count(cmd, &mut pos)?, Run::Fill(SlpPixel::Transparent)),
+                0x06 => (self.count(cmd, &mut pos)?, Run::Copy(SlpPixel::PlayerColor)),
+                0x07 | 0x0A => {
+                    let count = self.count(cmd, &mut pos)?;
+                    let idx = self.u8(pos)?;
+                    pos += 1;
+                    let pixel = if cmd & 0x0F == 0x07 { SlpPixel::Color(idx) } else { SlpPixel::PlayerColor(idx) };
+                    (count, Run::Fill(pixel))
+                },
+                0x0B => (self.count(cmd, &mut pos)?, Run::Fill(SlpPixel::Shadow)),
+                0x0E => match cmd {
+                    // hints for drawing mirrored frames, which don't affect the pixels
+                    0x0E | 0x1E | 0x2E | 0x3E => continue,
+                    0x4E => (1, Run::Fill(SlpPixel::PlayerOutline)),
+                    0x6E => (1, Run::Fill(SlpPixel::BlackOutline)),
+                    0x5E | 0x7E => {
+                        let count = self.u8(pos)? as usize;
+                        pos += 1;
+                        let pixel = if cmd == 0x5E { SlpPixel::PlayerOutline } else { SlpPixel::BlackOutline };
+                        (count, Run::Fill(pixel))
+                    },
+                    _ => return Err(Error::Parse(format!("Unsupported extended command 0x{cmd:02X}"))),
+                },
+                _ if cmd & 0x03 == 0 => (cmd as usize >> 2, Run::Copy(SlpPixel::Color)),
+                _ => (cmd as usize >> 2, Run::Fill(SlpPixel::Transparent)),
+            };
+
+            if x + count > row.len() {
+                return Err(Error::Parse(format!(
+                    "{count} pixels at column {x} exceed the row, which ends at column {}", row.len()
+                )));
+            }
+            match run {
+                Run::Copy(pixel) => {
+                    let indices = self.bytes(pos, count)?;
+                    pos += count;
+                    for (dst, &idx) in row[x..x + count].iter_mut().zip(indices) {
+                        *dst = pixel(idx);
+                    }
+                },
+                Run::Fill(pixel) => row[x..x + count].fill(pixel),
+            }
+            x += count;
+        }
+    }
+
+    /// The count of a command is in its upper 4 bits, or in the next byte if those are 0
+    fn count(&self, cmd: u8, pos: &mut usize) -> Result<usize> {
+        match cmd >> 4 {
+            0 => {
+                *pos += 1;
+                Ok(self.u8(*pos - 1)? as usize)
+            },
+            count => Ok(count as usize),
+        }
+    }
+
+    /// The count of the greater copy and skip commands is 12 bits, its upper 4 bits are those of the command
+    fn greater_count(&self, cmd: u8, pos: &mut usize) -> Result<usize> {
+        *pos += 1;
+        Ok(((cmd as usize & 0xF0) << 4) + self.u8(*pos - 1)? as usize)
+    }
+}
+
+/// The pixels drawn by a command, either read from the palette indices following it or all the same
+enum Run {
+    Copy(fn(u8) -> SlpPixel),
+    Fill(SlpPixel),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An SLP file with one frame whose rows are given as (left, right, commands), the tables follow the frame info
+    fn slp(width: i32, height: i32, rows: &[(u16, u16, &[u8])]) -> Vec<u8> {
+        let mut bytes = b"2.0N".to_vec();
+        bytes.extend_from_slice(&1_u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 24]);
+
+        let outline_table = 64;
+        let cmd_table = outline_table + rows.len() * 4;
+        for value in [cmd_table as i32, outline_table as i32, 0, 0, width, height, 3, 2] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for (left, right, _) in rows {
+            bytes.extend_from_slice(&left.to_le_bytes());
+            bytes.extend_from_slice(&right.to_le_bytes());
+        }
+        let mut offset = cmd_table + rows.len() * 4;
+        for (_, _, commands) in rows {
+            bytes.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += commands.len();
+        }
+        for (_, _, commands) in rows {
+            bytes.extend_from_slice(commands);
+        }
+        bytes
+    }
+
+    #[test]
+    fn reads_command_set() {
+        use SlpPixel::*;
+
+        let rows: [(u16, u16, &[u8]); 4] = [
+            // color list of 3, skip 1, fill 2 and end of row, after 1 pixel of left spacing
+            (1, 0, &[0x0C, 10, 11, 12, 0x05, 0x27, 20, 0x0F]),
+            // player color list of 2, player fill with the count in the next byte, shadow, outlines and a mirror hint
+            (0, 0, &[0x26, 1, 2, 0x0A, 3, 4, 0x1B, 0x4E, 0x0E, 0x6E, 0x0F]),
+            // greater skip and color list, outline runs and a fill with the count in the next byte
+            (0, 0, &[0x03, 2, 0x02, 1, 30, 0x5E, 2, 0x7E, 1, 0x07, 2, 40, 0x0F]),
+            (EMPTY_ROW, EMPTY_ROW, &[]),
+        ];
+        let file = SlpFile::from_bytes(&slp(8, 4, &rows)).unwrap();
+        assert_eq!(file.header.version, "2.0N");
+
+        let frame = &file.frames[0];
+        assert_eq!((frame.width, frame.height, frame.hotspot_x, frame.hotspot_y), (8, 4, 3, 2));
+        assert_eq!(frame.pixels[..8], [
+            Transparent, Color(10), Color(11), Color(12), Transparent, Color(20), Color(20), Transparent,
+        ]);
+        assert_eq!(frame.pixels[8..16], [
+            PlayerColor(1), PlayerColor(2), PlayerColor(4), PlayerColor(4), PlayerColor(4), Shadow, PlayerOutline,
+            BlackOutline,
+        ]);
+        assert_eq!(frame.pixels[16..24], [
+            Transparent, Transparent, Color(30), PlayerOutline, PlayerOutline, BlackOutline, Color(40), Color(40),
+        ]);
+        assert!(frame.pixels[24..].iter().all(|&pixel| pixel == Transparent));
+
+        let palette: Vec<[u8; 3]> = (0..=255).map(|i| [i, 0, 0]).collect();
+        let decoded = frame.decode(&palette, 1).unwrap();
+        assert_eq!(decoded.image.get_pixel(0, 1).0, [17, 0, 0, 255]);
+        assert_eq!(decoded.player_color.get_pixel(0, 1).0, MASK_SET.0);
+        assert_eq!(decoded.shadow.get_pixel(5, 1).0, MASK_SET.0);
+        assert_eq!(decoded.outline.get_pixel(3, 2).0, MASK_SET.0);
+        assert_eq!(decoded.image.get_pixel(0, 0).0, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rejects_oversized_frames() {
+        let rows: [(u16, u16, &[u8]); 1] = [(0, 0, &[0x0F])];
+        assert!(matches!(SlpFile::from_bytes(&slp(1 << 20, 1 << 20, &rows)), Err(Error::Parse(_))));
+        // the height must fit the outline and command tables
+        assert!(matches!(SlpFile::from_bytes(&slp(8, 200, &rows)), Err(Error::Parse(_))));
+    }
+}
@@ -0,0 +1,480 @@
+use std::path::PathBuf;
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use crate::error::{Error, Result};
+
+pub const SMX_LAYER_MAIN: u8 = 0x01;
+pub const SMX_LAYER_SHADOW: u8 = 0x02;
+pub const SMX_LAYER_OUTLINE: u8 = 0x04;
+/// Set in a frame's type when the pixels of its main layer are packed with 8to5 instead of 4plus1
+pub const FLAG_8TO5: u8 = 0x08;
+
+const SMX_SIGNATURE: &[u8; 4] = b"SMPX";
+/// Marks a row of the outline table that has no pixels, such rows have no commands
+const EMPTY_ROW: u16 = 0xFFFF;
+/// Layers larger than this in either dimension are rejected as corrupt before their pixels are allocated
+const MAX_FRAME_SIZE: u16 = 4096;
+
+const MASK_SET: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct SmxHeader {
+    pub version: u16,
+    pub num_frames: u16,
+    pub file_size_comp: u32,
+    pub file_size_uncomp: u32,
+    pub comment: String,
+}
+
+/// A pixel of an SMX layer before the palette is applied
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmxPixel {
+    Transparent,
+    /// A palette index, including the palette section in its upper bits
+    Color(u16),
+    /// A palette index into the player's colors, including the palette section in its upper bits
+    PlayerColor(u16),
+    /// The opacity of the shadow
+    Shadow(u8),
+    Outline,
+}
+
+/// The hotspot is the anchor of the layer, i.e. the pixel placed at the unit's position
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Debug)]
+pub struct SmxLayer {
+    pub width: u16,
+    pub height: u16,
+    pub hotspot_x: i16,
+    pub hotspot_y: i16,
+    pub unknown: u32,
+    /// The pixels in row-major order
+    pub pixels: Vec<SmxPixel>,
+}
+
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct SmxFrame {
+    pub frame_type: u8,
+    pub palette_number: u8,
+    pub uncomp_size: u32,
+
+    pub main_layer: Option<SmxLayer>,
+    pub shadow_layer: Option<SmxLayer>,
+    pub outline_layer: Option<SmxLayer>,
+}
+
+/// An SMX frame after the palette is applied, with the layers that `render_frames` takes. The player color mask has
+/// the size of the main layer and is white where it has player color pixels, the shadow stores its opacity in the red
+/// channel like the SLD shadow layer and the outline is white where it has pixels. A missing shadow or outline layer is
+/// an empty image aligned with the main layer
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Debug)]
+pub struct DecodedSmxFrame {
+    pub image: RgbaImage,
+    pub player_color: RgbaImage,
+    pub hotspot: (i32, i32),
+    pub shadow: RgbaImage,
+    pub shadow_hotspot: (i32, i32),
+    pub outline: RgbaImage,
+    pub outline_hotspot: (i32, i32),
+}
+
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct SmxFile {
+    pub header: SmxHeader,
+    pub frames: Vec<SmxFrame>,
+}
+
+impl SmxLayer {
+    fn decode(&self, mut color: impl FnMut(SmxPixel) -> Result<Option<Rgba<u8>>>) -> Result<RgbaImage> {
+        let mut image = RgbaImage::new(self.width as u32, self.height as u32);
+        for (idx, pixel) in self.pixels.iter().enumerate() {
+            if let Some(color) = color(*pixel)? {
+                image.put_pixel(idx as u32 % self.width as u32, idx as u32 / self.width as u32, color);
+            }
+        }
+        Ok(image)
+    }
+
+    fn hotspot(&self) -> (i32, i32) {
+        (self.hotspot_x as i32, self.hotspot_y as i32)
+    }
+}
+
+impl SmxFrame {
+    /// Applies the palette, player color pixels use `player_palette` if it is given
+    pub fn decode(&self, palette: &[[u8; 3]], player_palette: Option<&[[u8; 3]]>) -> Result<DecodedSmxFrame> {
+        let lookup = |palette: &[[u8; 3]], idx: u16| palette.get(idx as usize)
+            .map(|&[r, g, b]| Rgba([r, g, b, 255]))
+            .ok_or_else(|| Error::InvalidInput(format!(
+                "Palette index {idx} is out of range for a palette with {} colors", palette.len()
+            )));
+        let player_palette = player_palette.unwrap_or(palette);
+
+        let (image, player_color, hotspot) = match &self.main_layer {
+            Some(layer) => {
+                let image = layer.decode(|pixel| match pixel {
+                    SmxPixel::Color(idx) => Ok(Some(lookup(palette, idx)?)),
+                    SmxPixel::PlayerColor(idx) => Ok(Some(lookup(player_palette, idx)?)),
+                    _ => Ok(None),
+                })?;
+                let player_color = layer.decode(|pixel| {
+                    Ok(matches!(pixel, SmxPixel::PlayerColor(_)).then_some(MASK_SET))
+                })?;
+                (image, player_color, layer.hotspot())
+            },
+            None => (RgbaImage::new(0, 0), RgbaImage::new(0, 0), (0, 0)),
+        };
+        let empty = || (RgbaImage::new(image.width(), image.height()), hotspot);
+
+        let (shadow, shadow_hotspot) = match &self.shadow_layer {
+            Some(layer) => (
+                layer.decode(|pixel| match pixel {
+                    SmxPixel::Shadow(alpha) => Ok(Some(Rgba([alpha, alpha, alpha, 255]))),
+                    _ => Ok(None),
+                })?,
+                layer.hotspot(),
+            ),
+            None => empty(),
+        };
+
+        let (outline, outline_hotspot) = match &self.outline_layer {
+            Some(layer) => (
+                layer.decode(|pixel| Ok((pixel == SmxPixel::Outline).then_some(MASK_SET)))?,
+                layer.hotspot(),
+            ),
+            None => empty(),
+        };
+
+        Ok(DecodedSmxFrame { image, player_color, hotspot, shadow, shadow_hotspot, outline, outline_hotspot })
+    }
+}
+
+impl DecodedSmxFrame {
+    /// The position of the shadow relative to the main layer, as taken by `render_frames`
+    pub fn shadow_offset(&self) -> (i32, i32) {
+        (self.hotspot.0 - self.shadow_hotspot.0, self.hotspot.1 - self.shadow_hotspot.1)
+    }
+}
+
+impl SmxFile {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = SmxReader::new(bytes);
+
+        let signature = reader.bytes(4)?;
+        if signature != SMX_SIGNATURE {
+            return Err(Error::Parse(format!(
+                "Invalid SMX signature '{}'", String::from_utf8_lossy(signature)
+            )));
+        }
+
+        let header = SmxHeader {
+            version: reader.u16()?,
+            num_frames: reader.u16()?,
+            file_size_comp: reader.u32()?,
+            file_size_uncomp: reader.u32()?,
+            comment: String::from_utf8_lossy(reader.bytes(16)?).trim_end_matches('\0').to_string(),
+        };
+
+        let mut frames = Vec::with_capacity(header.num_frames as usize);
+        for _ in 0..header.num_frames {
+            frames.push(reader.frame()?);
+        }
+
+        Ok(Self { header, frames })
+    }
+
+    pub fn from_file(path: PathBuf) -> Result<Self> {
+        let bytes = std::fs::read(&path).map_err(|e| Error::Io(path.clone(), e))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Decodes every frame in parallel, see `SmxFrame::decode`
+    pub fn decode(&self, palette: &[[u8; 3]], player_palette: Option<&[[u8; 3]]>) -> Result<Vec<DecodedSmxFrame>> {
+        self.frames.par_iter()
+            .map(|frame| frame.decode(palette, player_palette))
+            .collect()
+    }
+}
+
+struct SmxReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SmxReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.pos + len > self.bytes.len() {
+            return Err(Error::Parse(format!(
+                "Unexpected end of SMX data: tried to read {len} bytes at offset {} but only {} bytes are left",
+                self.pos, self.bytes.len() - self.pos.min(self.bytes.len())
+            )));
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn i16(&mut self) -> Result<i16> {
+        Ok(i16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn frame(&mut self) -> Result<SmxFrame> {
+        let mut frame = SmxFrame {
+            frame_type: self.u8()?,
+            palette_number: self.u8()?,
+            uncomp_size: self.u32()?,
+            main_layer: None,
+            shadow_layer: None,
+            outline_layer: None,
+        };
+
+        for kind in [SMX_LAYER_MAIN, SMX_LAYER_SHADOW, SMX_LAYER_OUTLINE] {
+            if frame.frame_type & kind == 0 {
+                continue;
+            }
+            let layer = self.layer(kind, frame.frame_type & FLAG_8TO5 != 0)?;
+            match kind {
+                SMX_LAYER_MAIN => frame.main_layer = Some(layer),
+                SMX_LAYER_SHADOW => frame.shadow_layer = Some(layer),
+                _ => frame.outline_layer = Some(layer),
+            }
+        }
+
+        Ok(frame)
+    }
+
+    fn layer(&mut self, kind: u8, packed_8to5: bool) -> Result<SmxLayer> {
+        let start = self.pos;
+        let width = self.u16()?;
+        let height = self.u16()?;
+        let hotspot_x = self.i16()?;
+        let hotspot_y = self.i16()?;
+        let layer_len = self.u32()? as usize;
+        let unknown = self.u32()?;
+        if width > MAX_FRAME_SIZE || height > MAX_FRAME_SIZE {
+            return Err(Error::Parse(format!(
+                "Layer at offset {start} has a size of {width}x{height}, which exceeds the maximum of \
+                {MAX_FRAME_SIZE}x{MAX_FRAME_SIZE}"
+            )));
+        }
+
+        let mut data = SmxReader::new(self.bytes(layer_len)?);
+        let outlines = (0..height)
+            .map(|_| Ok((data.u16()?, data.u16()?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut pixels = vec![SmxPixel::Transparent; width as usize * height as usize];
+        let rows = pixels.chunks_exact_mut(width.max(1) as usize).zip(outlines);
+
+        if kind == SMX_LAYER_MAIN {
+            // both lengths precede the command array and the pixel array
+            let cmd_len = data.u32()? as usize;
+            let pixel_len = data.u32()? as usize;
+            let mut commands = SmxReader::new(data.bytes(cmd_len)?);
+            let mut colors = PackedPixels::new(data.bytes(pixel_len)?, packed_8to5);
+
+            for (y, (row, (left, right))) in rows.enumerate() {
+                if left == EMPTY_ROW || right == EMPTY_ROW {
+                    continue;
+                }
+                let end = row.len().saturating_sub(right as usize);
+                read_row(&mut commands, &mut row[..end], left as usize, |_, player_color| {
+                    let (idx, section) = colors.next()?;
+                    let idx = section as u16 * 256 + idx as u16;
+                    Ok(if player_color { SmxPixel::PlayerColor(idx) } else { SmxPixel::Color(idx) })
+                }).map_err(|e| Error::Parse(format!("Failed to read row {y} of the layer at offset {start}: {e}")))?;
+            }
+        } else {
+            let data_len = data.u32()? as usize;
+            let mut commands = SmxReader::new(data.bytes(data_len)?);
+
+            for (y, (row, (left, right))) in rows.enumerate() {
+                if left == EMPTY_ROW || right == EMPTY_ROW {
+                    continue;
+                }
+                let end = row.len().saturating_sub(right as usize);
+                read_row(&mut commands, &mut row[..end], left as usize, |commands, _| {
+                    // shadow pixels are followed by their opacity, outline pixels have no data
+                    Ok(match kind {
+                        SMX_LAYER_SHADOW => SmxPixel::Shadow(commands.u8()?),
+                        _ => SmxPixel::Outline,
+                    })
+                }).map_err(|e| Error::Parse(format!("Failed to read row {y} of the layer at offset {start}: {e}")))?;
+            }
+        }
+
+        Ok(SmxLayer { width, height, hotspot_x, hotspot_y, unknown, pixels })
+    }
+}
+
+/// Reads the commands of a row starting at `x` until its end of row command. `pixel` reads the pixels of the draw
+/// commands, whose flag is set for player color draws
+fn read_row(
+    commands: &mut SmxReader,
+    row: &mut [SmxPixel],
+    mut x: usize,
+    mut pixel: impl FnMut(&mut SmxReader, bool) -> Result<SmxPixel>,
+) -> Result<()> {
+    loop {
+        let cmd = commands.u8()?;
+        // the lower 2 bits are the command, the upper 6 bits are the count minus 1
+        let count = (cmd >> 2) as usize + 1;
+        if cmd & 0b11 == 0b11 {
+            return Ok(());
+        }
+
+        if x + count > row.len() {
+            return Err(Error::Parse(format!(
+                "{count} pixels at column {x} exceed the row, which ends at column {}", row.len()
+            )));
+        }
+        for dst in &mut row[x..x + count] {
+            *dst = match cmd & 0b11 {
+                0b00 => SmxPixel::Transparent,
+                0b01 => pixel(commands, false)?,
+                _ => pixel(commands, true)?,
+            };
+        }
+        x += count;
+    }
+}
+
+/// The palette indices and sections of the main layer's pixels. 4plus1 packs 4 pixels into 5 bytes, the indices
+/// followed by a byte with the 2 bit sections. 8to5 packs 2 pixels into 5 bytes, each pixel taking 20 bits: the index,
+/// the section and damage modifiers, which are not used
+struct PackedPixels<'a> {
+    bytes: &'a [u8],
+    packed_8to5: bool,
+    idx: usize,
+}
+
+impl<'a> PackedPixels<'a> {
+    fn new(bytes: &'a [u8], packed_8to5: bool) -> Self {
+        Self { bytes, packed_8to5, idx: 0 }
+    }
+
+    fn next(&mut self) -> Result<(u8, u8)> {
+        let per_chunk = if self.packed_8to5 { 2 } else { 4 };
+        let (chunk, i) = (self.idx / per_chunk, self.idx % per_chunk);
+        let Some(chunk) = self.bytes.get(chunk * 5..chunk * 5 + 5) else {
+            return Err(Error::Parse(format!(
+                "Pixel {} is out of range of the {} bytes of pixel data", self.idx, self.bytes.len()
+            )));
+        };
+        self.idx += 1;
+
+        if self.packed_8to5 {
+            let bits = chunk.iter().rev().fold(0_u64, |bits, &byte| bits << 8 | byte as u64);
+            let pixel = bits >> (20 * i);
+            Ok((pixel as u8, (pixel >> 8) as u8 & 0b11))
+        } else {
+            Ok((chunk[i], (chunk[4] >> (2 * i)) & 0b11))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A layer with the given row edges, command array and, for the main layer, pixel array
+    fn layer(width: u16, edges: &[(u16, u16)], commands: &[u8], colors: Option<&[u8]>) -> Vec<u8> {
+        let mut data: Vec<u8> = edges.iter().flat_map(|&(left, right)| [left.to_le_bytes(), right.to_le_bytes()])
+            .flatten()
+            .collect();
+        data.extend((commands.len() as u32).to_le_bytes());
+        if let Some(colors) = colors {
+            data.extend((colors.len() as u32).to_le_bytes());
+        }
+        data.extend(commands);
+        data.extend(colors.unwrap_or_default());
+
+        let mut bytes = Vec::new();
+        for value in [width, edges.len() as u16, 3, 1] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend((data.len() as u32).to_le_bytes());
+        bytes.extend(0_u32.to_le_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    /// A file with one frame holding a 4x2 main layer, whose second row is empty, and a 2x1 shadow layer. The main
+    /// layer draws 2 colors and 1 player color after skipping the first column
+    fn smx(frame_type: u8, colors: &[u8]) -> Vec<u8> {
+        file(frame_type | SMX_LAYER_MAIN | SMX_LAYER_SHADOW, &[
+            layer(4, &[(1, 0), (EMPTY_ROW, EMPTY_ROW)], &[1 << 2 | 0b01, 0b10, 0b11], Some(colors)),
+            layer(2, &[(0, 0)], &[1 << 2 | 0b01, 100, 200, 0b11], None),
+        ])
+    }
+
+    /// A file with one frame of the given type and layers
+    fn file(frame_type: u8, layers: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = SMX_SIGNATURE.to_vec();
+        bytes.extend(2_u16.to_le_bytes());
+        bytes.extend(1_u16.to_le_bytes());
+        bytes.extend([0; 8]);
+        bytes.extend([0; 16]);
+        bytes.extend([frame_type, 0]);
+        bytes.extend(0_u32.to_le_bytes());
+        bytes.extend(layers.concat());
+        bytes
+    }
+
+    fn assert_pixels(bytes: &[u8]) {
+        let file = SmxFile::from_bytes(bytes).unwrap();
+        let frame = &file.frames[0];
+        let main = frame.main_layer.as_ref().unwrap();
+        let shadow = frame.shadow_layer.as_ref().unwrap();
+        assert_eq!((main.hotspot_x, main.hotspot_y), (3, 1));
+        assert_eq!(main.pixels, [
+            SmxPixel::Transparent, SmxPixel::Color(266), SmxPixel::Color(20), SmxPixel::PlayerColor(542),
+            SmxPixel::Transparent, SmxPixel::Transparent, SmxPixel::Transparent, SmxPixel::Transparent,
+        ]);
+        assert_eq!(shadow.pixels, [SmxPixel::Shadow(100), SmxPixel::Shadow(200)]);
+        assert!(frame.outline_layer.is_none());
+    }
+
+    #[test]
+    fn reads_4plus1_pixels() {
+        // the indices of 4 pixels followed by their 2 bit sections, 1, 0 and 2
+        assert_pixels(&smx(0, &[10, 20, 30, 0, 0b10_00_01]));
+    }
+
+    #[test]
+    fn reads_8to5_pixels() {
+        // 2 pixels of 20 bits per 5 bytes, the index followed by the section
+        let pack = |a: u64, b: u64| (a | b << 20).to_le_bytes()[..5].to_vec();
+        let colors = [pack(1 << 8 | 10, 20), pack(2 << 8 | 30, 0)].concat();
+        assert_pixels(&smx(FLAG_8TO5, &colors));
+    }
+
+    #[test]
+    fn rejects_oversized_frames() {
+        let rows = [(EMPTY_ROW, EMPTY_ROW); MAX_FRAME_SIZE as usize + 1];
+        let bytes = file(SMX_LAYER_MAIN, &[layer(1, &rows, &[], Some(&[]))]);
+        assert!(matches!(SmxFile::from_bytes(&bytes), Err(Error::Parse(_))));
+    }
+}
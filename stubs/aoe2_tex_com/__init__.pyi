@@ -2,14 +2,66 @@ from .format import *
 from .encode_decode import *
 from .preprocess import *
 from .render import *
+from .palette import *
+from .sld import *
+from .slp import *
+from .smx import *
+from .animation import *
+from .batch import *
+from .metrics import *
+from .convert import *
+from .dds import *
 
 __all__ = [
     "encode",
     "decode",
     "preprocess",
     "render",
+    "encode_raw",
+    "decode_raw",
+    "preprocess_raw",
+    "preprocess_layers",
+    "preprocess_layers_raw",
+    "render_raw",
+    "decode_animation",
+    "encode_animation",
+    "encode_batch",
+    "decode_batch",
+    "encode_animations",
+    "decode_animations",
+    "measure_quality",
+    "compare_images",
+    "slp_to_sld",
+    "smx_to_sld",
+    "to_dds",
+    "encode_dds",
+    "decode_dds",
+    "decode_dds_raw",
 
     "BcQuality",
     "BcFormat",
+    "SkipMode",
+    "AlphaDither",
+    "EncodeOptions",
     "ProcessedFrame",
+    "LayerKind",
+    "DrawCall",
+    "SldFile",
+    "SldHeader",
+    "SldFrame",
+    "SldLayer",
+    "SldLayerHeader",
+    "SlpFile",
+    "SlpHeader",
+    "SlpFrame",
+    "DecodedSlpFrame",
+    "SmxFile",
+    "SmxHeader",
+    "SmxFrame",
+    "SmxLayer",
+    "DecodedSmxFrame",
+    "LayerDecoder",
+    "LayerEncoder",
+    "QualityMetrics",
+    "Palette",
 ]
@@ -0,0 +1,97 @@
+from aoe2_tex_com import BcFormat, BcQuality, DrawCall, EncodeOptions, ProcessedFrame, SldLayer
+
+class LayerDecoder:
+    """
+    Decodes the frames of one layer of an animation in order, keeping the blocks and offsets of the previous frame so
+    that frames which reuse the previous frame's blocks can be decoded
+    """
+
+    def __new__(cls):
+        ...
+
+    def reset(self):
+        """
+        Forgets the previous frame, for use when starting a new animation
+        """
+
+    def decode(
+        self,
+        bytes_: bytes,
+        offsets: tuple[int, int, int, int],
+        format: BcFormat,
+        commands: list[DrawCall],
+        use_prev: bool = False,
+    ) -> bytes:
+        """
+        Decode the next frame of the animation
+
+        :param bytes_: The drawn blocks of this frame
+        :param offsets: The (x1, y1, x2, y2) offsets of this frame on the canvas
+        :param format: The compression format
+        :param commands: Draw commands of the format [(num_blocks_skipped, num_blocks_drawn)]
+        :param use_prev: If true, skipped blocks are copied from the previously decoded frame
+
+        :return: The decoded image as PNG bytes
+        """
+
+    def decode_layer(self, layer: SldLayer) -> bytes:
+        """
+        Decode the next frame of the animation from an SLD layer
+
+        :return: The decoded image as PNG bytes
+        """
+
+def decode_animation(layers: list[SldLayer]) -> list[bytes]:
+    """
+    Decodes all frames of one layer of an animation
+
+    :param layers: The same layer of every frame in order
+
+    :return: The decoded images as PNG bytes
+    """
+
+class LayerEncoder:
+    """
+    Encodes the frames of one layer of an animation in order. Each frame is encoded both standalone and with skips
+    referencing the previous frame, and whichever draws fewer blocks is kept
+    """
+
+    def __new__(
+        cls,
+        format: BcFormat = BcFormat.Bc1,
+        quality: BcQuality = BcQuality.Slow,
+        options: EncodeOptions = None,
+    ):
+        ...
+
+    def reset(self):
+        """
+        Forgets the previous frame, for use when starting a new animation
+        """
+
+    def encode(self, frame: ProcessedFrame) -> tuple[list[DrawCall], bytes, bool]:
+        """
+        Encode the next frame of the animation
+
+        :param frame: The next frame returned by `preprocess`
+
+        :return: A tuple containing the draw commands, the drawn blocks and whether the frame references the previous
+                 frame (the SLD "use previous frame" flag). These can be passed to `SldLayer.from_encoded`
+        """
+
+def encode_animation(
+    frames: list[ProcessedFrame],
+    format: BcFormat = BcFormat.Bc1,
+    quality: BcQuality = BcQuality.Slow,
+    options: EncodeOptions = None,
+) -> list[tuple[list[DrawCall], bytes, bool]]:
+    """
+    Encodes all frames of one layer of an animation
+
+    :param frames: The frames returned by `preprocess`
+    :param format: The compression format
+    :param quality: Compression quality
+    :param options: Additional encoding options, see `EncodeOptions`
+
+    :return: For every frame, see `LayerEncoder.encode`
+    """
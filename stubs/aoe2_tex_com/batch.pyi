@@ -0,0 +1,63 @@
+from aoe2_tex_com.encode_decode import EncodeOptions
+from aoe2_tex_com.format import BcFormat, BcQuality, DrawCall
+from aoe2_tex_com.preprocess import ProcessedFrame
+from aoe2_tex_com.sld import SldLayer
+
+from typing import Optional
+
+def encode_batch(
+    images: list[bytes],
+    format: BcFormat = BcFormat.Bc1,
+    quality: BcQuality = BcQuality.Slow,
+    gen_commands: bool = False,
+    options: EncodeOptions = None,
+) -> list[tuple[bytes, Optional[tuple[list[DrawCall], bytes]]]]:
+    """
+    Encodes independent images in parallel without holding the GIL
+
+    :param images: The image bytes to encode (common image formats are supported)
+    :param format: The compression format
+    :param quality: Compression quality
+    :param gen_commands: If true, generates the skip/draw commands for use in SLD files
+    :param options: Additional encoding options, see `EncodeOptions`
+
+    :return: For every image in the same order, see `encode`
+    """
+
+def decode_batch(
+    frames: list[tuple[bytes, int, int, BcFormat, Optional[list[DrawCall]]]],
+) -> list[tuple[bytes, bytes]]:
+    """
+    Decodes independent frames in parallel without holding the GIL. Frames that reuse the previous frame's blocks
+    depend on each other and should be decoded with `decode_animations` instead
+
+    :param frames: A list of (bytes, width, height, format, commands), see `decode`
+
+    :return: For every frame in the same order, see `decode`
+    """
+
+def encode_animations(
+    animations: list[list[ProcessedFrame]],
+    format: BcFormat = BcFormat.Bc1,
+    quality: BcQuality = BcQuality.Slow,
+    options: EncodeOptions = None,
+) -> list[list[tuple[list[DrawCall], bytes, bool]]]:
+    """
+    Encodes independent animations (e.g. the directions or layers of a unit) in parallel without holding the GIL
+
+    :param animations: The frames of every animation returned by `preprocess`
+    :param format: The compression format
+    :param quality: Compression quality
+    :param options: Additional encoding options, see `EncodeOptions`
+
+    :return: For every animation in the same order, see `encode_animation`
+    """
+
+def decode_animations(animations: list[list[SldLayer]]) -> list[list[bytes]]:
+    """
+    Decodes independent animations in parallel without holding the GIL
+
+    :param animations: The layers of every animation in frame order
+
+    :return: For every animation in the same order, see `decode_animation`
+    """
@@ -0,0 +1,43 @@
+from aoe2_tex_com import BcQuality, EncodeOptions, SldFile, SlpFile, SmxFile
+
+from typing import Optional
+
+def slp_to_sld(
+    slp: SlpFile,
+    palette: list[tuple[int, int, int]],
+    player: int = 1,
+    quality: BcQuality = BcQuality.Slow,
+    options: Optional[EncodeOptions] = None,
+) -> SldFile:
+    """
+    Converts an SLP file to SLD. The player color mask is derived from the player color pixels and the shadow layer
+    from the shadow pixels, which are fully opaque. These layers are only written if some frame has such pixels. The
+    hotspots are kept as the anchors of the frames
+
+    :param slp:
+    :param palette: See `SlpFrame.decode`
+    :param player: See `SlpFrame.decode`
+    :param quality:
+    :param options:
+    :return:
+    """
+
+def smx_to_sld(
+    smx: SmxFile,
+    palette: list[tuple[int, int, int]],
+    player_palette: Optional[list[tuple[int, int, int]]] = None,
+    quality: BcQuality = BcQuality.Slow,
+    options: Optional[EncodeOptions] = None,
+) -> SldFile:
+    """
+    Converts an SMX file to SLD. The shadow layer keeps the opacity of the SMX shadow and is aligned with the main layer
+    through the hotspots, the player color mask is derived from the player color pixels. These layers are only written
+    if some frame has such pixels
+
+    :param smx:
+    :param palette: See `SmxFrame.decode`
+    :param player_palette: See `SmxFrame.decode`
+    :param quality:
+    :param options:
+    :return:
+    """
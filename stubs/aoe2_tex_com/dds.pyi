@@ -0,0 +1,60 @@
+from aoe2_tex_com import BcFormat, BcQuality, EncodeOptions
+
+from typing import Optional
+
+def to_dds(
+    blocks: bytes,
+    width: int,
+    height: int,
+    format: BcFormat,
+    mipmaps: int = 1,
+) -> bytes:
+    """
+    Wraps compressed blocks, e.g. those returned by encode, in a DDS file. BC1 uses the legacy DXT1 header that every
+    DDS reader supports, BC4 and BC7 use the DX10 header
+
+    :param blocks: The compressed blocks of every mipmap one after another, each half the size of the previous one
+    :param width: The width of the image
+    :param height: The height of the image
+    :param format: The compression format of the blocks
+    :param mipmaps: The number of mipmaps in blocks, including the image itself
+
+    :return: The bytes of the DDS file
+    """
+
+def encode_dds(
+    bytes_: bytes,
+    format: BcFormat = BcFormat.Bc1,
+    quality: BcQuality = BcQuality.Slow,
+    mipmaps: bool = False,
+    options: Optional[EncodeOptions] = None,
+) -> bytes:
+    """
+    Encodes the given image bytes into a DDS file, see to_dds
+
+    :param bytes_: The bytes to encode (common image formats are supported)
+    :param format: The compression format
+    :param quality: Compression quality
+    :param mipmaps: If true, generates the full chain of mipmaps down to 1x1 by downscaling the image and compresses
+                    every level the same way as the image
+    :param options: Additional encoding options, see EncodeOptions
+
+    :return: The bytes of the DDS file
+    """
+
+def decode_dds(bytes_: bytes) -> tuple[bytes, BcFormat, bytes]:
+    """
+    Reads a BC1, BC4 or BC7 DDS file and decompresses its first mipmap
+
+    :param bytes_: The bytes of the DDS file
+
+    :return: A tuple containing the image as PNG bytes, its compression format and its compressed blocks
+    """
+
+def decode_dds_raw(bytes_: bytes) -> tuple[bytes, int, int, BcFormat, bytes]:
+    """
+    Same as decode_dds, but returns the raw width * height RGBA pixels instead of PNG bytes
+
+    :return: A tuple containing the raw RGBA pixels, the width, the height, the compression format and the compressed
+             blocks
+    """
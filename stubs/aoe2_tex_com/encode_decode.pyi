@@ -1,7 +1,42 @@
-from aoe2_tex_com.format import BcFormat, BcQuality, DrawCall
+from aoe2_tex_com.format import AlphaDither, BcFormat, BcQuality, DrawCall, SkipMode
 
+from collections.abc import Buffer
 from typing import Optional
 
+class EncodeOptions:
+    skip_mode: SkipMode
+    max_delta: Optional[int]
+    max_mse: Optional[float]
+    alpha_cutoff: int
+    alpha_dither: AlphaDither
+    bleed_colors: bool
+
+    def __new__(
+        cls,
+        skip_mode: SkipMode = SkipMode.Bitwise,
+        max_delta: Optional[int] = None,
+        max_mse: Optional[float] = None,
+        alpha_cutoff: int = 128,
+        alpha_dither: AlphaDither = AlphaDither.Threshold,
+        bleed_colors: bool = False,
+    ):
+        """
+        :param skip_mode: How blocks are compared with the previous frame to decide whether they are skipped. With
+                          SkipMode.Pixels, blocks that decode to the same pixels are replaced by the previous frame's
+                          block, which is also reflected in the returned blocks
+        :param max_delta: Also skips blocks whose source pixels differ by at most this much in every channel from the
+                          previous frame's decoded block. This is lossy, the skipped blocks show the previous frame
+        :param max_mse: Also skips blocks whose source pixels have at most this mean squared error from the previous
+                        frame's decoded block. If both max_delta and max_mse are given, a block must be within both
+        :param alpha_cutoff: BC1 pixels with a lower alpha are transparent. This should match the cutoff given to
+                             preprocess so that cropping and the 1-bit alpha agree
+        :param alpha_dither: How semi-transparent pixels are turned into BC1's 1-bit alpha. Dithering keeps soft edges
+                             and effects like dust visible as a pattern instead of cutting them off at the cutoff
+        :param bleed_colors: Fills transparent pixels with the colors of nearby opaque pixels before compressing, so
+                             that compressed edges don't pick up dark fringes. Only changes BC7, as BC1 decodes
+                             transparent pixels to black and BC4 has no transparency
+        """
+
 def encode(
     bytes_: bytes,
     format: BcFormat = BcFormat.Bc1,
@@ -9,6 +44,7 @@ def encode(
     gen_commands: bool = False,
 
     prev_info: tuple[bytes, int, int, int, int] = None,
+    options: EncodeOptions = None,
 ) -> tuple[bytes, Optional[tuple[list[DrawCall], bytes]]]:
     """
     Encode the given image bytes using the format and the provided quality
@@ -25,6 +61,7 @@ def encode(
                       the previous frame *after* processing the previous frame's draw commands. This is the third
                       return value of this function, and it should be passed to the next decode call in this parameter
                       when required.
+    :param options: Additional encoding options, see EncodeOptions
 
     :return: A tuple containing the compressed texture blocks as bytes, and if gen_commands is set to True, an optional
              tuple which contains the num skip/draw blocks and the bytes after taking out all the bytes that should be
@@ -58,4 +95,44 @@ def decode(
 
     :return: A tuple containing the raw image bytes and the compressed blocks for this frame after the draw commands
              have been processed.
-    """
\ No newline at end of file
+    """
+
+def encode_raw(
+    pixels: Buffer,
+    width: int,
+    height: int,
+    format: BcFormat = BcFormat.Bc1,
+    quality: BcQuality = BcQuality.Slow,
+    gen_commands: bool = False,
+
+    prev_info: tuple[bytes, int, int, int, int] = None,
+    options: EncodeOptions = None,
+) -> tuple[bytes, Optional[tuple[list[DrawCall], bytes]]]:
+    """
+    Same as encode, but takes raw pixels instead of encoded image bytes. The pixels are copied before the GIL is
+    released for encoding, so the buffer may be modified meanwhile
+
+    :param pixels: A C-contiguous buffer of width * height RGBA pixels, e.g. bytes or a (height, width, 4) uint8 numpy
+                   array
+    :param width: The width of the image
+    :param height: The height of the image
+
+    :return: See encode
+    """
+
+def decode_raw(
+    bytes_: bytes,
+    width: int,
+    height: int,
+    format: BcFormat,
+    commands: list[DrawCall] = None,
+
+    prev_info: tuple[bytes, int, int, int, int] = None,
+) -> tuple[bytes, bytes]:
+    """
+    Same as decode, but returns the raw width * height RGBA pixels instead of PNG bytes. These can be turned into a
+    numpy array with np.frombuffer(pixels, np.uint8).reshape(height, width, 4)
+
+    :return: A tuple containing the raw RGBA pixels and the compressed blocks for this frame after the draw commands
+             have been processed.
+    """
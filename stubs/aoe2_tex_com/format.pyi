@@ -10,6 +10,25 @@ class BcFormat(IntEnum):
     Bc4 = 1
     Bc7 = 2
 
+class SkipMode(IntEnum):
+    """How a block is compared with the block at the same position in the previous frame to decide whether it is
+    skipped"""
+
+    Bitwise = 0
+    """The compressed blocks must be bit-for-bit identical"""
+    Pixels = 1
+    """The compressed blocks must decode to identical pixels, e.g. BC1 blocks with swapped endpoints"""
+
+class AlphaDither(IntEnum):
+    """How semi-transparent pixels are turned into BC1's 1-bit alpha"""
+
+    Threshold = 0
+    """Pixels below the alpha cutoff are transparent"""
+    Ordered = 1
+    """A 4x4 Bayer pattern around the alpha cutoff"""
+    ErrorDiffusion = 2
+    """Floyd-Steinberg error diffusion around the alpha cutoff"""
+
 class DrawCall:
     skip: int
     draw: int
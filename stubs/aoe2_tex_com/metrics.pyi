@@ -0,0 +1,54 @@
+from aoe2_tex_com.encode_decode import EncodeOptions
+from aoe2_tex_com.format import BcFormat, BcQuality
+
+from typing import Optional
+
+class QualityMetrics:
+    """
+    How much a decoded image differs from the original. The overall metrics compare premultiplied colors, so the color
+    of invisible pixels does not count, and the opaque metrics only consider the color of pixels that are not
+    transparent in the original. A PSNR of infinity means the images are identical
+    """
+
+    psnr: float
+    ssim: float
+    max_error: int
+    opaque_psnr: float
+    opaque_ssim: float
+    opaque_max_error: int
+    alpha_changed: Optional[int]
+    """The number of pixels whose alpha differs between the images, None for BC4, which has no alpha"""
+
+def measure_quality(
+    bytes_: bytes,
+    format: BcFormat = BcFormat.Bc1,
+    quality: BcQuality = BcQuality.Slow,
+    options: EncodeOptions = None,
+) -> QualityMetrics:
+    """
+    Compresses and decompresses an image and compares the result with the image
+
+    :param bytes_: The image bytes (common image formats are supported)
+    :param format: The compression format
+    :param quality: Compression quality
+    :param options: Additional encoding options, see EncodeOptions
+
+    :return: The quality metrics of the round-trip
+    """
+
+def compare_images(
+    original: bytes,
+    decoded: bytes,
+    format: BcFormat = BcFormat.Bc1,
+    alpha_cutoff: int = 128,
+) -> QualityMetrics:
+    """
+    Compares a decoded image with its original. Only the channels the format stores are compared, i.e. red for BC4
+
+    :param original: The original image bytes
+    :param decoded: The decoded image bytes, e.g. from decode
+    :param format: The compression format, which decides the compared channels and which pixels are transparent
+    :param alpha_cutoff: BC1 pixels with a lower alpha are transparent
+
+    :return: The quality metrics
+    """
@@ -0,0 +1,30 @@
+class Palette:
+    """
+    The colors of a palette, e.g. the game's palettes used by SLP and SMX sprites or a player color palette. The colors
+    can be passed to `SlpFile.decode` and `SmxFile.decode`
+    """
+    colors: list[tuple[int, int, int]]
+
+    def __new__(cls, colors: list[tuple[int, int, int]]):
+        ...
+
+    @staticmethod
+    def from_bytes(bytes_: bytes) -> Palette:
+        """
+        Parses a JASC-PAL palette, the format of the game's palettes including the player color palettes. Their colors
+        may have a fourth alpha value, which is ignored, and lines starting with # are comments
+        """
+
+    @staticmethod
+    def from_file(path: str) -> Palette: ...
+
+    def to_bytes(self) -> bytes:
+        """
+        Writes the palette as JASC-PAL
+        """
+
+    def to_file(self, path: str): ...
+
+    def color(self, index: int) -> tuple[int, int, int]: ...
+
+    def __len__(self) -> int: ...
@@ -1,8 +1,26 @@
 from aoe2_tex_com import BcFormat
 
+from collections.abc import Buffer, Callable
+from enum import IntEnum
+
+
+class LayerKind(IntEnum):
+    """The meaning of a layer of an SLD frame, which decides what an empty pixel is independently of the compression
+    format"""
+
+    Main = 0
+    """Pixels are empty below the alpha cutoff"""
+    Shadow = 1
+    """Pixels are empty when their red channel is 0"""
+    PlayerColor = 2
+    """Pixels are empty when their red channel is 0"""
+    DamageMask = 3
+    """Pixels are empty when their red, green and blue channels are 0"""
 
 class ProcessedFrame:
     image_bytes: bytes
+    image_raw: bytes
+    """The raw RGBA pixels of the image, which is (offset_x2 - offset_x1) x (offset_y2 - offset_y1)"""
     offset_x1: int
     offset_y1: int
     offset_x2: int
@@ -13,11 +31,75 @@ class ProcessedFrame:
 def preprocess(
     frame_info: list[tuple[str, tuple[int, int]]],
     format: BcFormat = BcFormat.Bc1,
+    alpha_cutoff: int = 128,
+    layer_kind: LayerKind | None = None,
+    is_empty: Callable[[int, int, int, int], bool] | None = None,
 ) -> tuple[list[ProcessedFrame], tuple[int, int], tuple[int, int]]:
     """
     Preprocesses frames to remove transparency and
 
     :param frame_info:
     :param format:
+    :param alpha_cutoff: BC1 pixels with a lower alpha are transparent and cropped, this should match the cutoff given
+                         to EncodeOptions
+    :param layer_kind: Decides which pixels are empty by the meaning of the layer instead of the format
+    :param is_empty: Decides which pixels are empty from their (r, g, b, a), can't be combined with layer_kind
+    :return:
+    """
+
+def preprocess_raw(
+    frame_info: list[tuple[Buffer, tuple[int, int], tuple[int, int]]],
+    format: BcFormat = BcFormat.Bc1,
+    alpha_cutoff: int = 128,
+    layer_kind: LayerKind | None = None,
+    is_empty: Callable[[int, int, int, int], bool] | None = None,
+) -> tuple[list[ProcessedFrame], tuple[int, int], tuple[int, int]]:
+    """
+    Same as preprocess, but takes raw pixels instead of image paths
+
+    :param frame_info: A list of (RGBA pixels, (width, height), anchor) for each frame
+    :param format:
+    :param alpha_cutoff: See preprocess
+    :param layer_kind: See preprocess
+    :param is_empty: See preprocess
     :return:
-    """
\ No newline at end of file
+    """
+
+def preprocess_layers(
+    frame_info: list[tuple[tuple[str, str | None, str | None, str | None], tuple[int, int]]],
+    alpha_cutoff: int = 128,
+) -> tuple[
+    list[tuple[ProcessedFrame, ProcessedFrame | None, ProcessedFrame | None, ProcessedFrame | None]],
+    tuple[int, int],
+    tuple[int, int],
+]:
+    """
+    Preprocesses the layers of SLD frames. The main layer is cropped like preprocess, the player color and damage masks
+    are cropped to the same rectangle as the main layer and the shadow is cropped to its own bounds. The canvas covers
+    the main layers and the shadows of all frames
+
+    :param frame_info: A list of ((main, shadow, player color, damage mask) image paths, anchor) for each frame, the
+                       layers of a frame share its anchor
+    :param alpha_cutoff: See preprocess
+    :return: The processed (main, shadow, player color, damage mask) layers of each frame, the anchor of the canvas
+             and the size of the canvas
+    """
+
+def preprocess_layers_raw(
+    frame_info: list[
+        tuple[tuple[Buffer, Buffer | None, Buffer | None, Buffer | None], tuple[int, int], tuple[int, int]]
+    ],
+    alpha_cutoff: int = 128,
+) -> tuple[
+    list[tuple[ProcessedFrame, ProcessedFrame | None, ProcessedFrame | None, ProcessedFrame | None]],
+    tuple[int, int],
+    tuple[int, int],
+]:
+    """
+    Same as preprocess_layers, but takes raw pixels instead of image paths
+
+    :param frame_info: A list of ((main, shadow, player color, damage mask) RGBA pixels, (width, height), anchor) for
+                       each frame
+    :param alpha_cutoff: See preprocess
+    :return: See preprocess_layers
+    """
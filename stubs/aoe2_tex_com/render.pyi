@@ -1,3 +1,8 @@
+from aoe2_tex_com import Palette
+
+from collections.abc import Buffer
+
+
 def render(
     main_layer: bytes,
     shadow_layer: bytes,
@@ -5,7 +10,51 @@ def render(
     player_color_mask: bytes,
     damage_mask: tuple[bytes, int] = None,
     color: tuple[int, int, int] = (255, 0, 0),
+    shadow_color: tuple[int, int, int] = (0, 0, 0),
+    shadow_opacity: float = 1.0,
+    palette: Palette = None,
+    player: int = None,
 ) -> bytes:
     """
     Renders an SLD frame given the different layers after decoding and a player color
+
+    :param damage_mask: A tuple containing the damage mask image (aligned with the main layer) and the damage
+                        percentage of the building. The mask's red, green and blue channels darken the pixels once
+                        25%, 50% and 75% damage is reached respectively
+    :param color: The player color
+    :param shadow_color: The color of the shadow, which is drawn underneath the unit
+    :param shadow_opacity: Scales the shadow layer's opacity, between 0 and 1
+    :param palette: Takes the player color from this palette instead of color. Without player, it is a player color
+                    palette ordered from dark to light, in which the luminance of the main layer picks the color
+    :param player: The index of the player color in the palette
+
+    :return: The rendered frame as PNG bytes, with straight alpha so that it can be pasted onto terrain
+    """
+
+def render_raw(
+    main_layer: Buffer,
+    main_size: tuple[int, int],
+    shadow_layer: Buffer,
+    shadow_size: tuple[int, int],
+    shadow_layer_offset: tuple[int, int],
+    player_color_mask: Buffer,
+    damage_mask: tuple[Buffer, int] = None,
+    color: tuple[int, int, int] = (255, 0, 0),
+    shadow_color: tuple[int, int, int] = (0, 0, 0),
+    shadow_opacity: float = 1.0,
+    palette: Palette = None,
+    player: int = None,
+) -> tuple[bytes, tuple[int, int]]:
+    """
+    Same as render, but takes and returns raw pixels instead of PNG bytes
+
+    :param main_layer: The RGBA pixels of the main layer, which is main_size (width, height)
+    :param shadow_layer: The single channel pixels of the shadow, which is shadow_size (width, height)
+    :param player_color_mask: The single channel pixels of the player color mask, which is main_size
+    :param damage_mask: A tuple containing the RGBA pixels of the damage mask, which is main_size, and the damage
+                        percentage of the building
+    :param palette: See render
+    :param player: See render
+
+    :return: A tuple containing the rendered RGBA pixels and their (width, height)
     """
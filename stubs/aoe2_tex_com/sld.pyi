@@ -0,0 +1,140 @@
+from aoe2_tex_com import BcFormat, DrawCall, ProcessedFrame
+
+from typing import Optional
+
+class SldHeader:
+    version: int
+    num_frames: int
+    unknown1: int
+    unknown2: int
+    unknown3: int
+
+class SldLayerHeader:
+    """
+    For the damage mask and player color layers, only flag1 and unknown1 are stored in the file, and the offsets are
+    those of the main layer of the same frame
+    """
+    offset_x1: int
+    offset_y1: int
+    offset_x2: int
+    offset_y2: int
+    flag1: int
+    unknown1: int
+
+    def __new__(
+        cls,
+        offset_x1: int,
+        offset_y1: int,
+        offset_x2: int,
+        offset_y2: int,
+        flag1: int,
+        unknown1: int = 0,
+    ):
+        ...
+
+class SldLayer:
+    header: SldLayerHeader
+    commands: list[DrawCall]
+    blocks: bytes
+    trailing: bytes
+    """
+    Bytes after the blocks that are counted in the layer's content length but not used by its commands. They are kept
+    so that a file is written back unchanged
+    """
+
+    def __new__(cls, header: SldLayerHeader, commands: list[DrawCall], blocks: bytes):
+        ...
+
+    @staticmethod
+    def from_encoded(
+        encoded: tuple[list[DrawCall], bytes],
+        frame: ProcessedFrame,
+        format: BcFormat = BcFormat.Bc1,
+        use_prev: bool = False,
+    ) -> SldLayer:
+        """
+        Creates a layer from the commands and blocks generated by `encode`
+
+        :param encoded: The second return value of `encode` when called with gen_commands set to True
+        :param frame: The frame whose offsets position this layer on the canvas. For the damage mask and player color
+                      layers, this must be the frame of the main layer
+        :param format: The compression format, SLD files only support BC1 and BC4
+        :param use_prev: Must be set if prev_info was passed to `encode` for this frame
+        """
+
+    @property
+    def width(self) -> int: ...
+    @property
+    def height(self) -> int: ...
+    @property
+    def format(self) -> BcFormat: ...
+    @property
+    def uses_prev(self) -> bool:
+        """
+        If true, skipped blocks are copied from the same layer of the previous frame
+        """
+
+    def decode(self, prev_info: tuple[bytes, int, int, int, int] = None) -> tuple[bytes, bytes]:
+        """
+        Decodes this layer, see `decode` for the meaning of prev_info and the return value
+        """
+
+class SldFrame:
+    canvas_width: int
+    canvas_height: int
+    canvas_hotspot_x: int
+    canvas_hotspot_y: int
+    frame_type: int
+    unknown1: int
+    frame_index: int
+
+    main_layer: Optional[SldLayer]
+    shadow_layer: Optional[SldLayer]
+    unknown_layer: Optional[SldLayer]
+    damage_mask_layer: Optional[SldLayer]
+    player_color_layer: Optional[SldLayer]
+
+    def __new__(
+        cls,
+        canvas_width: int,
+        canvas_height: int,
+        canvas_hotspot_x: int,
+        canvas_hotspot_y: int,
+        frame_index: int,
+        main_layer: Optional[SldLayer] = None,
+        shadow_layer: Optional[SldLayer] = None,
+        unknown_layer: Optional[SldLayer] = None,
+        damage_mask_layer: Optional[SldLayer] = None,
+        player_color_layer: Optional[SldLayer] = None,
+        unknown1: int = 0,
+    ):
+        """
+        The frame type is derived from the layers that are present. The canvas size and hotspot are the second and
+        first extra return values of `preprocess` respectively
+        """
+
+    def layers(self) -> list[tuple[int, SldLayer]]:
+        """
+        :return: The layers present in this frame in the order they are stored, paired with their layer type bit
+        """
+
+class SldFile:
+    header: SldHeader
+    frames: list[SldFrame]
+
+    def __new__(cls, frames: list[SldFrame], version: int = 4):
+        ...
+
+    @staticmethod
+    def from_bytes(bytes_: bytes) -> SldFile: ...
+
+    @staticmethod
+    def from_file(path: str) -> SldFile: ...
+
+    def to_bytes(self) -> bytes:
+        """
+        Serializes this file. Files read with `from_bytes` are written back byte for byte, except that the padding
+        after each layer is written as zeros
+        """
+
+    def to_file(self, path: str): ...
@@ -0,0 +1,57 @@
+class SlpHeader:
+    version: str
+    num_frames: int
+    comment: str
+
+class DecodedSlpFrame:
+    """
+    An SLP frame after the palette is applied. The shadow, player color and outline masks have the size of the image
+    and are white where the frame has such pixels, matching the red channel convention of the SLD mask layers
+    """
+    image_bytes: bytes
+    image_raw: bytes
+    """The raw RGBA pixels of the image"""
+    shadow_bytes: bytes
+    shadow_raw: bytes
+    player_color_bytes: bytes
+    player_color_raw: bytes
+    outline_bytes: bytes
+    outline_raw: bytes
+    width: int
+    height: int
+    hotspot: tuple[int, int]
+    """Can be used as the anchor of the frame in `preprocess`"""
+
+class SlpFrame:
+    """
+    The hotspot is the anchor of the frame, i.e. the pixel placed at the unit's position
+    """
+    width: int
+    height: int
+    hotspot_x: int
+    hotspot_y: int
+    palette_offset: int
+    properties: int
+
+    def decode(self, palette: list[tuple[int, int, int]], player: int = 1) -> DecodedSlpFrame:
+        """
+        Applies the palette
+
+        :param palette: The RGB colors of the palette
+        :param player: Player color pixels use the colors of this player, 1 to 8 in the default palettes
+        """
+
+class SlpFile:
+    header: SlpHeader
+    frames: list[SlpFrame]
+
+    @staticmethod
+    def from_bytes(bytes_: bytes) -> SlpFile: ...
+
+    @staticmethod
+    def from_file(path: str) -> SlpFile: ...
+
+    def decode(self, palette: list[tuple[int, int, int]], player: int = 1) -> list[DecodedSlpFrame]:
+        """
+        Decodes every frame in parallel, see `SlpFrame.decode`
+        """
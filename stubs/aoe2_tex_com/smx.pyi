@@ -0,0 +1,80 @@
+from typing import Optional
+
+class SmxHeader:
+    version: int
+    num_frames: int
+    file_size_comp: int
+    file_size_uncomp: int
+    comment: str
+
+class SmxLayer:
+    """
+    The hotspot is the anchor of the layer, i.e. the pixel placed at the unit's position
+    """
+    width: int
+    height: int
+    hotspot_x: int
+    hotspot_y: int
+    unknown: int
+
+class DecodedSmxFrame:
+    """
+    An SMX frame after the palette is applied, with the layers that `render` takes. The player color mask has the size
+    of the main layer and is white where it has player color pixels, the shadow stores its opacity in the red channel
+    like the SLD shadow layer and the outline is white where it has pixels. A missing shadow or outline layer is an
+    empty image aligned with the main layer
+    """
+    image_bytes: bytes
+    image_raw: bytes
+    """The raw RGBA pixels of the main layer"""
+    player_color_bytes: bytes
+    player_color_raw: bytes
+    shadow_bytes: bytes
+    shadow_raw: bytes
+    outline_bytes: bytes
+    outline_raw: bytes
+    hotspot: tuple[int, int]
+    shadow_hotspot: tuple[int, int]
+    outline_hotspot: tuple[int, int]
+    shadow_offset: tuple[int, int]
+    """The position of the shadow relative to the main layer, as taken by `render`"""
+
+class SmxFrame:
+    frame_type: int
+    palette_number: int
+    uncomp_size: int
+
+    main_layer: Optional[SmxLayer]
+    shadow_layer: Optional[SmxLayer]
+    outline_layer: Optional[SmxLayer]
+
+    def decode(
+        self,
+        palette: list[tuple[int, int, int]],
+        player_palette: Optional[list[tuple[int, int, int]]] = None,
+    ) -> DecodedSmxFrame:
+        """
+        Applies the palette
+
+        :param palette: The RGB colors of the palette, including all of its sections
+        :param player_palette: The colors of player color pixels, which use palette if not given
+        """
+
+class SmxFile:
+    header: SmxHeader
+    frames: list[SmxFrame]
+
+    @staticmethod
+    def from_bytes(bytes_: bytes) -> SmxFile: ...
+
+    @staticmethod
+    def from_file(path: str) -> SmxFile: ...
+
+    def decode(
+        self,
+        palette: list[tuple[int, int, int]],
+        player_palette: Optional[list[tuple[int, int, int]]] = None,
+    ) -> list[DecodedSmxFrame]:
+        """
+        Decodes every frame in parallel, see `SmxFrame.decode`
+        """
@@ -0,0 +1,48 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use image::{Rgba, RgbaImage};
+
+/// An empty directory for the files of one test
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("aoe2_tex_com_cli_{}_{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn run(args: &[&Path]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_aoe2-tex-com")).args(args).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn extracts_built_frames() {
+    let dir = temp_dir("build_extract");
+    let colors = [Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255])];
+    let frames: Vec<_> = colors.iter()
+        .enumerate()
+        .map(|(i, &color)| {
+            let path = dir.join(format!("frame_{i}.png"));
+            RgbaImage::from_pixel(8, 8, color).save(&path).unwrap();
+            path
+        })
+        .collect();
+
+    let sld = dir.join("unit.sld");
+    let mut args = ["build", "--anchor", "4,4", "--quality", "fast"].map(Path::new).to_vec();
+    args.push(&sld);
+    args.extend(frames.iter().map(PathBuf::as_path));
+    run(&args);
+
+    let out_dir = dir.join("out");
+    run(&[Path::new("extract"), &sld, &out_dir]);
+
+    for (i, color) in colors.iter().enumerate() {
+        let image = image::open(out_dir.join(format!("unit_{i:03}_m.png"))).unwrap().into_rgba8();
+        assert_eq!(image.dimensions(), (8, 8));
+        assert!(image.pixels().all(|pixel| pixel == color), "frame {i}");
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}